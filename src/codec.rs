@@ -0,0 +1,407 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+///формат кадров /ws выбирается при подключении: параметром ?encoding=json|msgpack или подпротоколом json|msgpack.
+///
+///json идёт текстовыми кадрами, msgpack - бинарными. Схема msgpack повторяет json: каждый кадр - map
+///со строковыми ключами и теми же полями (channel, topic, seq, type, data у данных, event у ответов на подписку),
+///перечисления - строки в `snake_case`, отсутствующее значение - nil, uuid - bin из 16 байт, время - строка RFC 3339.
+///Запросы подписки принимаются в обоих форматах независимо от выбранного
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::encode::Error),
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::decode::Error),
+    UnsupportedFrame,
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "json: {e}"),
+            Self::MessagePack(e) => write!(f, "msgpack: {e}"),
+        }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "json: {e}"),
+            Self::MessagePack(e) => write!(f, "msgpack: {e}"),
+            Self::UnsupportedFrame => write!(f, "unsupported frame"),
+        }
+    }
+}
+
+impl Encoding {
+    ///подпротоколы в порядке предпочтения сервера
+    pub const PROTOCOLS: [&str; 2] = ["json", "msgpack"];
+
+    #[must_use]
+    pub fn from_protocol(protocol: &str) -> Option<Self> {
+        match protocol {
+            "json" => Some(Self::Json),
+            "msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    ///# Errors
+    ///`EncodeError`, если сообщение не сериализуется
+    pub fn encode_binary<T: Serialize>(self, message: &T) -> Result<Vec<u8>, EncodeError> {
+        match self {
+            Self::Json => serde_json::to_vec(message).map_err(EncodeError::Json),
+            Self::MessagePack => rmp_serde::to_vec_named(message).map_err(EncodeError::MessagePack),
+        }
+    }
+
+    ///декодер для клиентов: кадр сервера разбирается в `ChannelFrame` или `SubscriptionResponse`
+    ///# Errors
+    ///`DecodeError`, если кадр не разбирается в `T`
+    pub fn decode<T: DeserializeOwned>(self, frame: &[u8]) -> Result<T, DecodeError> {
+        match self {
+            Self::Json => serde_json::from_slice(frame).map_err(DecodeError::Json),
+            Self::MessagePack => rmp_serde::from_slice(frame).map_err(DecodeError::MessagePack),
+        }
+    }
+}
+
+///данные кадра websocket без привязки к клиентской библиотеке; служебные кадры сюда не попадают
+#[derive(Clone, Copy, Debug)]
+pub enum Frame<'a> {
+    Text(&'a str),
+    Binary(&'a [u8]),
+}
+
+///кадр разбирается по типу: текстовый как json, бинарный как msgpack
+///# Errors
+///`DecodeError`, если кадр не разбирается в `T`
+pub fn decode_frame<T: DeserializeOwned>(frame: Frame<'_>) -> Result<T, DecodeError> {
+    match frame {
+        Frame::Binary(bytes) => Encoding::MessagePack.decode(bytes),
+        Frame::Text(text) => Encoding::Json.decode(text.as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::best_bid_offer::BestBidOffer;
+    use crate::matching::models::candle::{Candle, CandleInterval};
+    use crate::matching::models::deal::Deal;
+    use crate::matching::models::depth_of_market::{DepthChange, DepthEntry, DepthOfMarket};
+    use crate::matching::models::execution_report::ExecutionReport;
+    use crate::matching::models::fee_schedule::Liquidity;
+    use crate::matching::models::feed_message::FeedMessage;
+    use crate::matching::models::market_by_order::{
+        BookOrder, MarketByOrder, OrderEvent, OrderEventKind,
+    };
+    use crate::matching::models::order_message::OrderMessage;
+    use crate::matching::models::order_record::OrderRecord;
+    use crate::matching::models::order_side::OrderSide;
+    use crate::matching::models::subscription_message::{
+        Channel, ChannelFrame, ChannelMessage, SubscriptionError, SubscriptionResponse,
+    };
+    use crate::matching::models::ticker::Ticker;
+    use crate::matching::models::trade_feed::{PrivateTrade, PublicTrade};
+    use chrono::{DateTime, Utc};
+    use std::fmt::Debug;
+    use uuid::Uuid;
+
+    const ENCODINGS: [Encoding; 2] = [Encoding::Json, Encoding::MessagePack];
+
+    fn round_trip<T>(channel: Channel, message: &FeedMessage<T>)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        for encoding in ENCODINGS {
+            let encoded = encoding
+                .encode_binary(&ChannelMessage {
+                    channel,
+                    topic: "BTCUSDT",
+                    message,
+                })
+                .unwrap();
+            let decoded: ChannelFrame<T> = encoding.decode(&encoded).unwrap();
+
+            assert_eq!(decoded.channel, channel);
+            assert_eq!(decoded.topic, "BTCUSDT");
+            assert_eq!(&decoded.message, message, "{encoding:?}");
+        }
+    }
+
+    #[test]
+    fn test_from_protocol() {
+        assert_eq!(
+            Encoding::from_protocol("msgpack"),
+            Some(Encoding::MessagePack)
+        );
+        assert_eq!(Encoding::from_protocol("json"), Some(Encoding::Json));
+        assert_eq!(Encoding::from_protocol("cbor"), None);
+    }
+
+    #[test]
+    fn test_decode_frame_by_type() {
+        let request = SubscriptionResponse::Error {
+            request_id: Some(String::from("r1")),
+            reason: SubscriptionError::InvalidMessage,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let msgpack = Encoding::MessagePack.encode_binary(&request).unwrap();
+
+        assert_eq!(
+            decode_frame::<SubscriptionResponse>(Frame::Text(&json)).unwrap(),
+            request
+        );
+        assert_eq!(
+            decode_frame::<SubscriptionResponse>(Frame::Binary(&msgpack)).unwrap(),
+            request
+        );
+        assert!(decode_frame::<SubscriptionResponse>(Frame::Binary(json.as_bytes())).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_depth() {
+        let entry = DepthEntry {
+            price: 100,
+            quantity: 5,
+            orders: 2,
+        };
+        round_trip(
+            Channel::Depth,
+            &FeedMessage::snapshot(
+                1,
+                DepthOfMarket {
+                    ask: vec![entry.clone()],
+                    bid: vec![DepthEntry { price: 99, ..entry }],
+                },
+            ),
+        );
+        round_trip(
+            Channel::Depth,
+            &FeedMessage::update(
+                2,
+                vec![DepthChange {
+                    side: OrderSide::Bid,
+                    price: 99,
+                    quantity: 0,
+                    orders: 0,
+                }],
+            ),
+        );
+    }
+
+    fn deal(ask_order: Uuid) -> Deal {
+        Deal {
+            id: 12,
+            match_id: 7,
+            side: OrderSide::Bid,
+            time: Utc::now(),
+            price: 101,
+            quantity: 2,
+            ask_order,
+            bid_order: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_trades() {
+        let trade = PublicTrade::from(&deal(Uuid::new_v4()));
+        round_trip(Channel::Trades, &FeedMessage::update(3, vec![trade]));
+    }
+
+    #[test]
+    fn test_round_trip_best_bid_offer() {
+        round_trip(
+            Channel::Bbo,
+            &FeedMessage::update(
+                4,
+                BestBidOffer {
+                    bid_price: Some(99),
+                    bid_quantity: Some(5),
+                    last_price: Some(100),
+                    last_quantity: Some(1),
+                    ..BestBidOffer::default()
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn test_round_trip_market_by_order() {
+        round_trip(
+            Channel::MarketByOrder,
+            &FeedMessage::snapshot(
+                5,
+                MarketByOrder {
+                    ask: vec![BookOrder {
+                        order_ref: 1,
+                        price: 101,
+                        quantity: 3,
+                    }],
+                    bid: Vec::new(),
+                },
+            ),
+        );
+        round_trip(
+            Channel::MarketByOrder,
+            &FeedMessage::update(
+                6,
+                vec![OrderEvent {
+                    kind: OrderEventKind::Execute,
+                    order_ref: 1,
+                    side: OrderSide::Ask,
+                    price: 101,
+                    quantity: 2,
+                }],
+            ),
+        );
+    }
+
+    #[test]
+    fn test_round_trip_orders() {
+        let order = OrderRecord::new(&OrderMessage {
+            id: Uuid::new_v4(),
+            account: Some(String::from("alice")),
+            side: OrderSide::Ask,
+            quantity: 3,
+            price: 101,
+        });
+        let fill = ExecutionReport::fill(&order, &deal(order.id), Liquidity::Maker, 3);
+        round_trip(Channel::Orders, &FeedMessage::update(7, fill.clone()));
+        round_trip(
+            Channel::Orders,
+            &FeedMessage::update(8, ExecutionReport::cancel(&order)),
+        );
+        round_trip(
+            Channel::Fills,
+            &FeedMessage::update(9, PrivateTrade::from_report(&fill).unwrap()),
+        );
+    }
+
+    #[test]
+    fn test_round_trip_candles() {
+        let open_time = DateTime::from_timestamp(1_700_000_040, 0).unwrap();
+        let candle = Candle {
+            interval: CandleInterval::OneMinute,
+            open_time,
+            close_time: open_time + CandleInterval::OneMinute.duration(),
+            open: 100,
+            high: 103,
+            low: 99,
+            close: 102,
+            volume: 12,
+            trades: 4,
+            closed: false,
+        };
+        round_trip(
+            Channel::Candles,
+            &FeedMessage::snapshot(10, vec![candle.clone()]),
+        );
+        round_trip(
+            Channel::Candles,
+            &FeedMessage::update(
+                11,
+                Candle {
+                    closed: true,
+                    ..candle
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn test_round_trip_ticker() {
+        round_trip(
+            Channel::Ticker,
+            &FeedMessage::update(
+                12,
+                Ticker {
+                    open: Some(100),
+                    high: Some(104),
+                    low: Some(98),
+                    last: Some(102),
+                    volume: 8,
+                    quote_volume: 808,
+                    trades: 3,
+                    price_change: Some(2),
+                    price_change_percent: Some(2.0),
+                    vwap: Some(101.0),
+                },
+            ),
+        );
+        round_trip(
+            Channel::Ticker,
+            &FeedMessage::snapshot(13, Ticker::default()),
+        );
+    }
+
+    #[test]
+    fn test_round_trip_subscription_responses() {
+        let responses = [
+            SubscriptionResponse::Subscribed {
+                request_id: Some(String::from("s1")),
+                channel: Channel::Depth,
+                topic: String::from("BTCUSDT"),
+            },
+            SubscriptionResponse::Unsubscribed {
+                request_id: None,
+                channel: Channel::Orders,
+                topic: String::from("alice"),
+            },
+            SubscriptionResponse::Error {
+                request_id: None,
+                reason: SubscriptionError::AlreadySubscribed,
+            },
+        ];
+        for encoding in ENCODINGS {
+            for response in &responses {
+                let encoded = encoding.encode_binary(response).unwrap();
+                let decoded: SubscriptionResponse = encoding.decode(&encoded).unwrap();
+                assert_eq!(&decoded, response);
+            }
+        }
+    }
+
+    #[test]
+    fn test_message_pack_is_more_compact() {
+        let message = FeedMessage::update(
+            9,
+            (0..10)
+                .map(|price| DepthChange {
+                    side: OrderSide::Ask,
+                    price,
+                    quantity: 10,
+                    orders: 1,
+                })
+                .collect::<Vec<_>>(),
+        );
+        let message = ChannelMessage {
+            channel: Channel::Depth,
+            topic: "BTCUSDT",
+            message: &message,
+        };
+
+        let json = Encoding::Json.encode_binary(&message).unwrap();
+        let msgpack = Encoding::MessagePack.encode_binary(&message).unwrap();
+
+        assert!(msgpack.len() < json.len());
+    }
+}
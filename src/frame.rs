@@ -0,0 +1,110 @@
+//! кадры axum поверх кодека библиотеки
+use crate::codec::{DecodeError, EncodeError, Encoding, Frame, decode_frame};
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::subscription_message::{Channel, ChannelMessage};
+use axum::body::Bytes;
+use axum::extract::ws::{Message, Utf8Bytes};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, OnceLock};
+
+///json идёт текстовым кадром, msgpack - бинарным
+pub fn encode<T: Serialize>(encoding: Encoding, message: &T) -> Result<Message, EncodeError> {
+    match encoding {
+        Encoding::Json => serde_json::to_string(message)
+            .map(|json| Message::Text(json.into()))
+            .map_err(EncodeError::Json),
+        Encoding::MessagePack => encoding
+            .encode_binary(message)
+            .map(|bytes| Message::Binary(bytes.into())),
+    }
+}
+
+///служебные кадры не разбираются: `DecodeError::UnsupportedFrame`
+pub fn decode_message<T: DeserializeOwned>(message: &Message) -> Result<T, DecodeError> {
+    match message {
+        Message::Text(text) => decode_frame(Frame::Text(text.as_str())),
+        Message::Binary(bytes) => decode_frame(Frame::Binary(bytes)),
+        _ => Err(DecodeError::UnsupportedFrame),
+    }
+}
+
+///сообщение канала, сериализованное один раз для всех подписчиков;
+///json кодируется сразу, msgpack - при первой отправке подписчику с этим форматом
+#[derive(Debug)]
+pub struct EncodedFeed<T> {
+    pub message: FeedMessage<T>,
+    pub json: Utf8Bytes,
+    channel: Channel,
+    topic: String,
+    msgpack: OnceLock<Bytes>,
+}
+
+pub type SharedFeed<T> = Arc<EncodedFeed<T>>;
+
+impl<T: Serialize> EncodedFeed<T> {
+    ///# Errors
+    ///ошибка сериализации json
+    pub fn new(channel: Channel, topic: &str, message: FeedMessage<T>) -> serde_json::Result<Self> {
+        let json = serde_json::to_string(&ChannelMessage {
+            channel,
+            topic,
+            message: &message,
+        })?;
+        Ok(Self {
+            message,
+            json: json.into(),
+            channel,
+            topic: topic.to_owned(),
+            msgpack: OnceLock::new(),
+        })
+    }
+
+    ///# Errors
+    ///`EncodeError`, если кадр msgpack не сериализуется
+    pub fn frame(&self, encoding: Encoding) -> Result<Message, EncodeError> {
+        match encoding {
+            Encoding::Json => Ok(Message::Text(self.json.clone())),
+            Encoding::MessagePack => {
+                if let Some(bytes) = self.msgpack.get() {
+                    return Ok(Message::Binary(bytes.clone()));
+                }
+                let bytes = Bytes::from(encoding.encode_binary(&ChannelMessage {
+                    channel: self.channel,
+                    topic: &self.topic,
+                    message: &self.message,
+                })?);
+                Ok(Message::Binary(self.msgpack.get_or_init(|| bytes).clone()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_types() {
+        assert!(matches!(
+            encode(Encoding::Json, &1).unwrap(),
+            Message::Text(_)
+        ));
+        assert!(matches!(
+            encode(Encoding::MessagePack, &1).unwrap(),
+            Message::Binary(_)
+        ));
+    }
+
+    #[test]
+    fn test_decode_message_by_type() {
+        for encoding in [Encoding::Json, Encoding::MessagePack] {
+            let frame = encode(encoding, &7_u32).unwrap();
+            assert_eq!(decode_message::<u32>(&frame).unwrap(), 7);
+        }
+        assert!(matches!(
+            decode_message::<u32>(&Message::Ping(Bytes::new())),
+            Err(DecodeError::UnsupportedFrame)
+        ));
+    }
+}
@@ -1,71 +1,1821 @@
 use crate::AppState;
+use crate::codec::Encoding;
+use crate::frame::{EncodedFeed, SharedFeed, decode_message, encode};
+use crate::idempotency::IdempotencyLookup;
+use crate::matching::models::cancel_filter::CancelFilter;
+use crate::matching::models::candle::{Candle, CandleInterval, CandlesQuery};
+use crate::matching::models::depth_book::DepthBook;
+use crate::matching::models::depth_of_market::{DepthOfMarket, DepthQuery};
+use crate::matching::models::engine_command::EngineCommand;
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::market_by_order::MarketByOrder;
+use crate::matching::models::order_ack::OrderAck;
 use crate::matching::models::order_message::OrderMessage;
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::order_status::OrderStatus;
+use crate::matching::models::reject_reason::RejectReason;
+use crate::matching::models::subscription_message::{
+    Channel, ChannelMessage, Subscription, SubscriptionError, SubscriptionRequest,
+    SubscriptionResponse,
+};
+use crate::matching::models::trade_feed::{PrivateTrade, PublicTrade};
+use crate::matching::models::trade_history::TradesQuery;
+use crate::matching::models::trade_message::{TradeRequest, TradeResponse};
+use crate::matching::replay::missed_trades;
+use crate::session::{CloseReason, SessionSender, run_session};
 use axum::{
     Json,
-    extract::State,
-    extract::ws::{WebSocket, WebSocketUpgrade},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     response::Response,
+    response::sse::{Event, KeepAlive, Sse},
 };
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::future::{Future, pending as pending_forever};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::{AbortHandle, JoinSet};
+use tokio::time::{MissedTickBehavior, interval_at};
+use uuid::Uuid;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const API_KEY_HEADER: &str = "X-Api-Key";
+const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+#[derive(Deserialize)]
+pub struct OrdersQuery {
+    account: Option<String>,
+    status: Option<OrderStatus>,
+}
+
+///счёт сессии определяется ключом из X-Api-Key или `api_key`, формат ответов - как у /ws;
+///`cancel_on_disconnect` - при обрыве соединения отменить все открытые ордера счёта
+#[derive(Deserialize)]
+pub struct TradeSessionQuery {
+    api_key: Option<String>,
+    encoding: Option<Encoding>,
+    #[serde(default)]
+    cancel_on_disconnect: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DepositRequest {
+    asset: String,
+    amount: u64,
+}
+
+#[derive(Deserialize)]
+pub struct TickerQuery {
+    symbol: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StreamsQuery {
+    encoding: Option<Encoding>,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct EventStreamQuery {
+    instrument: Option<String>,
+    history: Option<usize>,
+    conflation_ms: Option<u64>,
+    interval: Option<CandleInterval>,
+}
+
+#[derive(Debug)]
+struct EngineUnavailable;
+
+impl IntoResponse for EngineUnavailable {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Matching engine is unavailable",
+        )
+            .into_response()
+    }
+}
+
+///счёт по ключу из заголовка X-Api-Key, иначе из параметра: браузер не задаёт заголовки websocket.
+///Ok(None) - ключ не передан, неизвестный ключ - 401
+fn api_key_account(
+    state: &AppState,
+    headers: &HeaderMap,
+    query_key: Option<&str>,
+) -> Result<Option<String>, StatusCode> {
+    let api_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(query_key);
+    api_key
+        .map(|key| {
+            state
+                .api_keys
+                .get(key)
+                .cloned()
+                .ok_or(StatusCode::UNAUTHORIZED)
+        })
+        .transpose()
+}
+
+///счёт вызывающего REST-метода: без ключа в X-Api-Key запрос не выполняется
+fn caller_account(state: &AppState, headers: &HeaderMap) -> Result<String, StatusCode> {
+    api_key_account(state, headers, None)?.ok_or(StatusCode::UNAUTHORIZED)
+}
+
+fn is_admin(state: &AppState, headers: &HeaderMap) -> bool {
+    let admin_key = headers
+        .get(ADMIN_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    admin_key.is_some() && admin_key == state.admin_key.as_deref()
+}
+
+///счёт, указанный в запросе, должен совпадать со счётом ключа
+fn own_account(caller: String, requested: Option<&str>) -> Result<String, StatusCode> {
+    match requested {
+        Some(requested) if requested != caller => Err(StatusCode::FORBIDDEN),
+        _ => Ok(caller),
+    }
+}
+
+async fn request_engine<T>(
+    state: &AppState,
+    command: impl FnOnce(oneshot::Sender<T>) -> EngineCommand,
+) -> Result<T, EngineUnavailable> {
+    let (reply, reply_receiver) = oneshot::channel();
+    state
+        .engine_command_sender
+        .send(command(reply))
+        .await
+        .map_err(|_| EngineUnavailable)?;
+    reply_receiver.await.map_err(|_| EngineUnavailable)
+}
 
 pub async fn healthcheck() -> impl IntoResponse {
     (axum::http::StatusCode::OK,)
 }
 
+///ответ 201 только для принятого ордера, отклонённый движком получает 422 с причиной;
+///по Idempotency-Key запоминаются только принятые ордера, отклонённый можно отправить снова
 pub async fn create_order(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(message): Json<OrderMessage>,
-) -> impl IntoResponse {
-    let _ = (*state.order_message_sender).send(message);
-    (axum::http::StatusCode::CREATED,)
+) -> Response {
+    let account = caller_account(&state, &headers)
+        .and_then(|caller| own_account(caller, message.account.as_deref()));
+    let message = match account {
+        Ok(account) => OrderMessage {
+            account: Some(account),
+            ..message
+        },
+        Err(status) => return status.into_response(),
+    };
+    let Some(key) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return match place_order(&state, message).await {
+            Ok(ack) => (StatusCode::CREATED, Json(ack)).into_response(),
+            Err(response) => response,
+        };
+    };
+
+    let Ok(key) = key.to_str() else {
+        return (StatusCode::BAD_REQUEST, "Invalid Idempotency-Key header").into_response();
+    };
+
+    let account = message.account.clone().unwrap_or_default();
+    loop {
+        let lookup = match state.idempotency_cache.lock() {
+            Ok(mut cache) => cache.lookup(&account, key, &message, Instant::now()),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        match lookup {
+            IdempotencyLookup::Replay(ack) => {
+                return (StatusCode::CREATED, Json(ack)).into_response();
+            }
+            IdempotencyLookup::Conflict => {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "Idempotency-Key was already used with a different order",
+                )
+                    .into_response();
+            }
+            //после ack повторный lookup вернёт Replay, после отказа - освободившийся ключ
+            IdempotencyLookup::Pending(mut placing) => {
+                let _ = placing.changed().await;
+            }
+            IdempotencyLookup::New(placed) => {
+                return match place_order(&state, message).await {
+                    Ok(ack) => {
+                        placed.send_replace(Some(ack.clone()));
+                        (StatusCode::CREATED, Json(ack)).into_response()
+                    }
+                    Err(response) => response,
+                };
+            }
+        }
+    }
 }
 
-pub async fn get_orderbook(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| handle_orderbook(socket, state))
+async fn place_order(state: &AppState, order: OrderMessage) -> Result<OrderAck, Response> {
+    let id = order.id;
+    match request_engine(state, |reply| EngineCommand::Place { order, reply }).await {
+        Ok(Ok(_)) => Ok(OrderAck::new(id)),
+        Ok(Err(reason)) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(reason)).into_response()),
+        Err(e) => Err(e.into_response()),
+    }
 }
 
-async fn handle_orderbook(mut socket: WebSocket, state: AppState) {
-    loop {
-        let mut dom_receiver = (*state.orderbook_receiver).resubscribe();
-        match dom_receiver.recv().await {
-            Ok(dom) => match serde_json::to_string(&dom) {
-                Ok(json_string) => {
-                    if let Err(e) = socket.send(json_string.into()).await {
-                        eprintln!("Error sending orderbook: {e:?}");
+///чужой ордер неотличим от несуществующего
+pub async fn get_order(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Response {
+    let caller = match caller_account(&state, &headers) {
+        Ok(caller) => caller,
+        Err(status) => return status.into_response(),
+    };
+    match request_engine(&state, |reply| EngineCommand::GetOrder { id, reply }).await {
+        Ok(Some(order)) if order.account.as_deref() == Some(caller.as_str()) => {
+            Json(order).into_response()
+        }
+        Ok(_) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_orders(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<OrdersQuery>,
+) -> Response {
+    let account = match caller_account(&state, &headers)
+        .and_then(|caller| own_account(caller, query.account.as_deref()))
+    {
+        Ok(account) => account,
+        Err(status) => return status.into_response(),
+    };
+    let command = |reply| EngineCommand::GetOrders {
+        account: Some(account),
+        status: query.status,
+        reply,
+    };
+    match request_engine(&state, command).await {
+        Ok(orders) => Json(orders).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+///с админ-ключом снимаются ордера по любому фильтру, с API-ключом - только ордера своего счёта
+pub async fn cancel_orders(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(filter): Query<CancelFilter>,
+) -> Response {
+    let filter = if is_admin(&state, &headers) {
+        filter
+    } else {
+        match caller_account(&state, &headers)
+            .and_then(|caller| own_account(caller, filter.account.as_deref()))
+        {
+            Ok(account) => CancelFilter {
+                account: Some(account),
+                ..filter
+            },
+            Err(status) => return status.into_response(),
+        }
+    };
+    match request_engine(&state, |reply| EngineCommand::MassCancel { filter, reply }).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_balances(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(account): Path<String>,
+) -> Response {
+    let account = match caller_account(&state, &headers)
+        .and_then(|caller| own_account(caller, Some(&account)))
+    {
+        Ok(account) => account,
+        Err(status) => return status.into_response(),
+    };
+    match request_engine(&state, |reply| EngineCommand::GetBalances {
+        account,
+        reply,
+    })
+    .await
+    {
+        Ok(balances) => Json(balances).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+///служебный метод: зачисление доступно только с ключом администратора в заголовке X-Admin-Key
+pub async fn deposit(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(account): Path<String>,
+    Json(request): Json<DepositRequest>,
+) -> Response {
+    if !is_admin(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let command = |reply| EngineCommand::Deposit {
+        account,
+        asset: request.asset,
+        amount: request.amount,
+        reply,
+    };
+    match request_engine(&state, command).await {
+        Ok(Ok(balances)) => Json(balances).into_response(),
+        Ok(Err(reason)) => (StatusCode::UNPROCESSABLE_ENTITY, Json(reason)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_sessions(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.session_counters.stats())
+}
+
+async fn request_depth(state: &AppState) -> Result<FeedMessage<DepthOfMarket>, EngineUnavailable> {
+    request_engine(state, |reply| EngineCommand::GetDepth { reply }).await
+}
+
+pub async fn get_orderbook_snapshot(
+    State(state): State<AppState>,
+    Query(query): Query<DepthQuery>,
+) -> Response {
+    match request_depth(&state).await {
+        Ok(snapshot) if query.is_full() => Json(snapshot).into_response(),
+        Ok(snapshot) => {
+            let view = snapshot.data.view(&query);
+            let checksum = view.checksum();
+            Json(FeedMessage::snapshot(snapshot.seq, view).with_checksum(checksum)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn request_market_by_order(
+    state: &AppState,
+) -> Result<FeedMessage<MarketByOrder>, EngineUnavailable> {
+    request_engine(state, |reply| EngineCommand::GetMarketByOrder { reply }).await
+}
+
+pub async fn get_market_by_order_snapshot(State(state): State<AppState>) -> Response {
+    match request_market_by_order(&state).await {
+        Ok(snapshot) => Json(snapshot).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_trades(
+    State(state): State<AppState>,
+    Query(query): Query<TradesQuery>,
+) -> Response {
+    if query
+        .symbol
+        .as_ref()
+        .is_some_and(|symbol| *symbol != state.symbol)
+    {
+        return (StatusCode::NOT_FOUND, "Unknown symbol").into_response();
+    }
+    match request_engine(&state, |reply| EngineCommand::GetTrades { query, reply }).await {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_candles(
+    State(state): State<AppState>,
+    Query(query): Query<CandlesQuery>,
+) -> Response {
+    if query
+        .symbol
+        .as_ref()
+        .is_some_and(|symbol| *symbol != state.symbol)
+    {
+        return (StatusCode::NOT_FOUND, "Unknown symbol").into_response();
+    }
+    let Ok(book) = state.candle_book.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    Json(book.candles(&query)).into_response()
+}
+
+pub async fn get_ticker(
+    State(state): State<AppState>,
+    Query(query): Query<TickerQuery>,
+) -> Response {
+    if query
+        .symbol
+        .as_ref()
+        .is_some_and(|symbol| *symbol != state.symbol)
+    {
+        return (StatusCode::NOT_FOUND, "Unknown symbol").into_response();
+    }
+    let ticker = state.ticker_receiver.borrow().message.clone();
+    Json(ticker).into_response()
+}
+
+type SubscriptionKey = (Channel, String);
+
+///формат задаётся параметром encoding, иначе подпротоколом, по умолчанию json.
+///Ключ из заголовка X-Api-Key или параметра `api_key` привязывает соединение к счёту:
+///каналы orders и fills доступны только по своему счёту, без ключа - только рыночные каналы
+pub async fn get_streams(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(query): Query<StreamsQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let account = match api_key_account(&state, &headers, query.api_key.as_deref()) {
+        Ok(account) => account,
+        Err(status) => return status.into_response(),
+    };
+    let (ws, encoding) = negotiate_encoding(ws, query.encoding);
+    ws.on_upgrade(move |socket| handle_streams(socket, state, encoding, account))
+}
+
+fn negotiate_encoding(
+    ws: WebSocketUpgrade,
+    encoding: Option<Encoding>,
+) -> (WebSocketUpgrade, Encoding) {
+    let ws = ws.protocols(Encoding::PROTOCOLS);
+    let encoding = encoding
+        .or_else(|| {
+            ws.selected_protocol()
+                .and_then(|protocol| protocol.to_str().ok())
+                .and_then(Encoding::from_protocol)
+        })
+        .unwrap_or_default();
+    (ws, encoding)
+}
+
+///одно соединение на клиента: каждая подписка - отдельная задача, пишущая в общую очередь сессии
+async fn handle_streams(
+    socket: WebSocket,
+    state: AppState,
+    encoding: Encoding,
+    account: Option<String>,
+) {
+    let session = |sender: SessionSender, mut requests: mpsc::Receiver<Message>| {
+        let state = state.clone();
+        let account = account.clone();
+        async move {
+            let mut subscriptions: HashMap<SubscriptionKey, AbortHandle> = HashMap::new();
+            let mut streams: JoinSet<CloseReason> = JoinSet::new();
+            loop {
+                tokio::select! {
+                    request = requests.recv() => {
+                        let Some(frame) = request else {
+                            return CloseReason::ClientClosed;
+                        };
+                        let handled = handle_subscription_request(
+                            &state,
+                            &sender,
+                            encoding,
+                            account.as_deref(),
+                            &mut subscriptions,
+                            &mut streams,
+                            &frame,
+                        );
+                        if let Err(reason) = handled {
+                            return reason;
+                        }
                     }
+                    Some(Ok(reason)) = streams.join_next() => return reason,
                 }
-                Err(e) => {
-                    eprintln!("Error serializing orderbook to JSON: {e:?}");
+            }
+        }
+    };
+    run_session(
+        socket,
+        state.session_config,
+        &state.session_counters,
+        session,
+    )
+    .await;
+}
+
+///приватный канал открывается только по счёту, к которому привязано соединение
+fn authorize(
+    channel: Channel,
+    topic: String,
+    account: Option<&str>,
+) -> Result<String, SubscriptionError> {
+    match channel {
+        Channel::Orders | Channel::Fills if account != Some(topic.as_str()) => {
+            Err(SubscriptionError::Unauthorized)
+        }
+        _ => Ok(topic),
+    }
+}
+
+///подтверждение уходит до запуска задачи, чтобы клиент не получил данные раньше него
+fn handle_subscription_request(
+    state: &AppState,
+    sender: &SessionSender,
+    encoding: Encoding,
+    account: Option<&str>,
+    subscriptions: &mut HashMap<SubscriptionKey, AbortHandle>,
+    streams: &mut JoinSet<CloseReason>,
+    frame: &Message,
+) -> Result<(), CloseReason> {
+    let Ok(request) = decode_message::<SubscriptionRequest>(frame) else {
+        return send_message(
+            sender,
+            encoding,
+            &SubscriptionResponse::Error {
+                request_id: None,
+                reason: SubscriptionError::InvalidMessage,
+            },
+        );
+    };
+
+    match request {
+        SubscriptionRequest::Subscribe {
+            request_id,
+            subscription,
+        } => {
+            let topic = match subscription
+                .topic(&state.symbol)
+                .and_then(|topic| authorize(subscription.channel, topic, account))
+            {
+                Ok(topic) => topic,
+                Err(reason) => {
+                    return send_message(
+                        sender,
+                        encoding,
+                        &SubscriptionResponse::Error { request_id, reason },
+                    );
                 }
+            };
+            let key = (subscription.channel, topic.clone());
+            if subscriptions.contains_key(&key) {
+                return send_message(
+                    sender,
+                    encoding,
+                    &SubscriptionResponse::Error {
+                        request_id,
+                        reason: SubscriptionError::AlreadySubscribed,
+                    },
+                );
+            }
+
+            send_message(
+                sender,
+                encoding,
+                &SubscriptionResponse::Subscribed {
+                    request_id,
+                    channel: subscription.channel,
+                    topic: topic.clone(),
+                },
+            )?;
+            let channel_sender = ChannelSender {
+                sender: sender.clone(),
+                encoding,
+                channel: subscription.channel,
+                topic,
+            };
+            let stream = streams.spawn(stream_channel(state.clone(), channel_sender, subscription));
+            subscriptions.insert(key, stream);
+            Ok(())
+        }
+        SubscriptionRequest::Unsubscribe {
+            request_id,
+            subscription,
+        } => {
+            let response = match subscription.topic(&state.symbol) {
+                Ok(topic) => match subscriptions.remove(&(subscription.channel, topic.clone())) {
+                    Some(stream) => {
+                        stream.abort();
+                        SubscriptionResponse::Unsubscribed {
+                            request_id,
+                            channel: subscription.channel,
+                            topic,
+                        }
+                    }
+                    None => SubscriptionResponse::Error {
+                        request_id,
+                        reason: SubscriptionError::NotSubscribed,
+                    },
+                },
+                Err(reason) => SubscriptionResponse::Error { request_id, reason },
+            };
+            send_message(sender, encoding, &response)
+        }
+    }
+}
+
+///те же потоки, что и в /ws, для клиентов за прокси без websocket: каждый кадр - data одного события
+pub async fn get_event_stream(
+    Path(channel): Path<Channel>,
+    Query(query): Query<EventStreamQuery>,
+    Query(depth): Query<DepthQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    if !matches!(
+        channel,
+        Channel::Depth | Channel::Trades | Channel::Bbo | Channel::Candles | Channel::Ticker
+    ) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let subscription = Subscription {
+        channel,
+        instrument: Some(query.instrument.unwrap_or_else(|| state.symbol.clone())),
+        account: None,
+        history: query.history,
+        conflation_ms: query.conflation_ms,
+        interval: query.interval,
+        depth,
+    };
+    let topic = match subscription.topic(&state.symbol) {
+        Ok(topic) => topic,
+        Err(reason) => return (StatusCode::NOT_FOUND, Json(reason)).into_response(),
+    };
+
+    let (outbound, receiver) = mpsc::channel(state.session_config.outbound_buffer);
+    let sender = ChannelSender {
+        sender: SessionSender::new(outbound),
+        encoding: Encoding::Json,
+        channel,
+        topic,
+    };
+    //поток живёт, пока клиент читает события: JoinSet снимает задачу при закрытии соединения
+    let mut streams = JoinSet::new();
+    streams.spawn(stream_channel(state, sender, subscription));
+    let events = stream::unfold((receiver, streams), |(mut receiver, streams)| async move {
+        loop {
+            if let Message::Text(text) = receiver.recv().await? {
+                let event = Event::default().data(text.as_str());
+                return Some((Ok::<_, Infallible>(event), (receiver, streams)));
+            }
+        }
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn stream_channel(
+    state: AppState,
+    sender: ChannelSender,
+    subscription: Subscription,
+) -> CloseReason {
+    match subscription.channel {
+        Channel::Depth if subscription.depth.is_full() && subscription.conflation().is_none() => {
+            let receiver = (*state.orderbook_receiver).resubscribe();
+            let Ok(snapshot) = request_depth(&state).await else {
+                return CloseReason::ServerShutdown;
+            };
+            let resync = move || {
+                let state = state.clone();
+                async move { request_depth(&state).await.ok() }
+            };
+            stream_feed(&sender, receiver, Some(snapshot), resync).await
+        }
+        Channel::Depth => {
+            let conflation = subscription.conflation();
+            stream_depth_view(&sender, &state, &subscription.depth, conflation).await
+        }
+        Channel::Trades => stream_trades(&sender, &state, subscription.history).await,
+        Channel::Bbo => stream_latest(&sender, state.best_bid_offer_receiver.clone()).await,
+        Channel::Ticker => stream_latest(&sender, state.ticker_receiver.clone()).await,
+        Channel::Candles => {
+            //topic() не пропускает подписку на candles без интервала
+            let Some(interval) = subscription.interval else {
+                return CloseReason::ServerShutdown;
+            };
+            let query = CandlesQuery::latest(interval, subscription.history.unwrap_or(1));
+            stream_candles(&sender, &state, &query).await
+        }
+        Channel::MarketByOrder => {
+            let receiver = (*state.market_by_order_receiver).resubscribe();
+            let Ok(snapshot) = request_market_by_order(&state).await else {
+                return CloseReason::ServerShutdown;
+            };
+            let resync = move || {
+                let state = state.clone();
+                async move { request_market_by_order(&state).await.ok() }
+            };
+            stream_feed(&sender, receiver, Some(snapshot), resync).await
+        }
+        Channel::Orders => stream_account(&sender, &state, Some, true).await,
+        Channel::Fills => {
+            let fill = |report: ExecutionReport| PrivateTrade::from_report(&report);
+            stream_account(&sender, &state, fill, false).await
+        }
+    }
+}
+
+fn recent_deals(
+    snapshot: &FeedMessage<Vec<PublicTrade>>,
+    count: usize,
+) -> FeedMessage<Vec<PublicTrade>> {
+    let skipped = snapshot.data.len().saturating_sub(count);
+    FeedMessage::snapshot(snapshot.seq, snapshot.data[skipped..].to_vec())
+}
+
+struct ChannelSender {
+    sender: SessionSender,
+    encoding: Encoding,
+    channel: Channel,
+    topic: String,
+}
+
+impl ChannelSender {
+    fn send<T: Serialize>(&self, message: &FeedMessage<T>) -> Result<(), CloseReason> {
+        send_message(
+            &self.sender,
+            self.encoding,
+            &ChannelMessage {
+                channel: self.channel,
+                topic: &self.topic,
+                message,
             },
+        )
+    }
+
+    ///сообщение уже сериализовано издателем вместе с channel и topic
+    fn send_encoded<T: Serialize>(&self, encoded: &EncodedFeed<T>) -> Result<(), CloseReason> {
+        match encoded.frame(self.encoding) {
+            Ok(frame) => self.sender.send(frame),
             Err(e) => {
-                eprintln!("Error receiving orderbook: {e:?}");
+                eprintln!("Error serializing {:?} message: {e}", self.channel);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn send_message<T: Serialize>(
+    sender: &SessionSender,
+    encoding: Encoding,
+    message: &T,
+) -> Result<(), CloseReason> {
+    match encode(encoding, message) {
+        Ok(frame) => sender.send(frame),
+        Err(e) => {
+            eprintln!("Error serializing message: {e}");
+            Ok(())
+        }
+    }
+}
+
+///снимок берётся после подписки на обновления, поэтому вошедшие в него обновления пропускаются по seq;
+///при отставании снимок отправляется заново
+async fn stream_feed<U, S, F, Fut>(
+    sender: &ChannelSender,
+    mut receiver: broadcast::Receiver<SharedFeed<U>>,
+    snapshot: Option<FeedMessage<S>>,
+    resync: F,
+) -> CloseReason
+where
+    U: Serialize + Send + Sync,
+    S: Serialize + Send,
+    F: Fn() -> Fut + Send,
+    Fut: Future<Output = Option<FeedMessage<S>>> + Send,
+{
+    let mut last_seq = 0_u64;
+    if let Some(snapshot) = snapshot {
+        last_seq = snapshot.seq;
+        if let Err(reason) = sender.send(&snapshot) {
+            return reason;
+        }
+    }
+    loop {
+        let sent = match receiver.recv().await {
+            Ok(encoded) if encoded.message.seq > last_seq => {
+                last_seq = encoded.message.seq;
+                sender.send_encoded(&encoded)
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!(
+                    "Resyncing {:?} after skipping {skipped} messages",
+                    sender.channel
+                );
+                let Some(snapshot) = resync().await else {
+                    return CloseReason::ServerShutdown;
+                };
+                last_seq = snapshot.seq;
+                sender.send(&snapshot)
             }
+            Err(RecvError::Closed) => return CloseReason::ServerShutdown,
+        };
+        if let Err(reason) = sent {
+            return reason;
         }
     }
 }
 
-pub async fn get_deals(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| handle_deals(socket, state))
+///после отставания пропущенные сделки досылаются из истории движка по id последней отправленной;
+///если история их уже не покрывает, клиенту нужно переподписаться
+async fn stream_trades(
+    sender: &ChannelSender,
+    state: &AppState,
+    history: Option<usize>,
+) -> CloseReason {
+    let mut receiver = (*state.dealbook_receiver).resubscribe();
+    //снимок, seq и id последней сделки берутся из одного состояния издателя
+    let (snapshot, mut last_seq, mut last_trade_id) = {
+        let current = state.dealbook_snapshot.borrow();
+        (
+            history.map(|count| recent_deals(&current, count)),
+            current.seq,
+            current.data.last().map_or(0, |trade| trade.id),
+        )
+    };
+    if let Some(snapshot) = snapshot
+        && let Err(reason) = sender.send(&snapshot)
+    {
+        return reason;
+    }
+    loop {
+        let sent = match receiver.recv().await {
+            Ok(encoded) if encoded.message.seq > last_seq => {
+                last_seq = encoded.message.seq;
+                let trades = &encoded.message.data;
+                let sent = if trades.iter().all(|trade| trade.id > last_trade_id) {
+                    sender.send_encoded(&encoded)
+                } else {
+                    //часть сделок уже дослана из истории
+                    let fresh: Vec<&PublicTrade> = trades
+                        .iter()
+                        .filter(|trade| trade.id > last_trade_id)
+                        .collect();
+                    if fresh.is_empty() {
+                        continue;
+                    }
+                    sender.send(&FeedMessage::update(last_seq, fresh))
+                };
+                if let Some(last) = trades.last() {
+                    last_trade_id = last_trade_id.max(last.id);
+                }
+                sent
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!("Backfilling trades after skipping {skipped} messages");
+                //всё, что издатель разослал до этого seq, движок уже записал в историю
+                last_seq = last_seq.max(state.dealbook_snapshot.borrow().seq);
+                let Some(missed) = missed_trades(&state.engine_command_sender, last_trade_id).await
+                else {
+                    return CloseReason::ServerShutdown;
+                };
+                if missed
+                    .first()
+                    .is_some_and(|first| first.id > last_trade_id + 1)
+                {
+                    return CloseReason::Lagged;
+                }
+                let Some(last) = missed.last() else {
+                    continue;
+                };
+                last_trade_id = last.id;
+                sender.send(&FeedMessage::update(last_seq, missed))
+            }
+            Err(RecvError::Closed) => return CloseReason::ServerShutdown,
+        };
+        if let Err(reason) = sent {
+            return reason;
+        }
+    }
 }
 
-async fn handle_deals(mut socket: WebSocket, state: AppState) {
+async fn request_depth_book(state: &AppState) -> Result<(u64, DepthBook), EngineUnavailable> {
+    let snapshot = request_depth(state).await?;
+    Ok((snapshot.seq, DepthBook::new(&snapshot.data)))
+}
+
+///при depth или group клиент получает только снимки своего представления, и только когда оно изменилось;
+///с интервалом конфляции снимок отправляется не чаще раза за интервал и всегда по последнему состоянию
+async fn stream_depth_view(
+    sender: &ChannelSender,
+    state: &AppState,
+    query: &DepthQuery,
+    conflation: Option<Duration>,
+) -> CloseReason {
+    let mut dom_receiver = (*state.orderbook_receiver).resubscribe();
+    let Ok((mut last_seq, mut book)) = request_depth_book(state).await else {
+        return CloseReason::ServerShutdown;
+    };
+    let mut view = book.view(query);
+    if let Err(reason) =
+        sender.send(&FeedMessage::snapshot(last_seq, &view).with_checksum(view.checksum()))
+    {
+        return reason;
+    }
+
+    let mut ticker = conflation.map(|period| {
+        let mut ticker = interval_at(tokio::time::Instant::now() + period, period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+    let mut pending = false;
     loop {
-        let mut db_receiver = (*state.dealbook_receiver).resubscribe();
-        match db_receiver.recv().await {
-            Ok(dealbook) => match serde_json::to_string(&dealbook.deals) {
-                Ok(json_string) => {
-                    if let Err(e) = socket.send(json_string.into()).await {
-                        eprintln!("Error sending dealbook: {e:?}");
+        let tick = async {
+            match ticker.as_mut() {
+                Some(ticker) => ticker.tick().await,
+                None => pending_forever().await,
+            }
+        };
+        tokio::select! {
+            received = dom_receiver.recv() => {
+                match received {
+                    Ok(encoded) if encoded.message.seq > last_seq => {
+                        last_seq = encoded.message.seq;
+                        book.apply(&encoded.message.data);
+                        if encoded.message.checksum.is_some_and(|checksum| checksum != book.checksum()) {
+                            eprintln!("Resyncing depth view after checksum mismatch at seq {last_seq}");
+                            let Ok(resynced) = request_depth_book(state).await else {
+                                return CloseReason::ServerShutdown;
+                            };
+                            (last_seq, book) = resynced;
+                        }
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Resyncing depth view after skipping {skipped} messages");
+                        let Ok(resynced) = request_depth_book(state).await else {
+                            return CloseReason::ServerShutdown;
+                        };
+                        (last_seq, book) = resynced;
                     }
+                    Err(RecvError::Closed) => return CloseReason::ServerShutdown,
                 }
-                Err(e) => {
-                    eprintln!("Error serializing dealbook to JSON: {e:?}");
+                if ticker.is_some() {
+                    pending = true;
+                    continue;
                 }
+            }
+            _ = tick, if pending => pending = false,
+        }
+
+        let next_view = book.view(query);
+        if next_view == view {
+            continue;
+        }
+        view = next_view;
+        if let Err(reason) =
+            sender.send(&FeedMessage::snapshot(last_seq, &view).with_checksum(view.checksum()))
+        {
+            return reason;
+        }
+    }
+}
+
+fn candles_snapshot(state: &AppState, query: &CandlesQuery) -> Option<FeedMessage<Vec<Candle>>> {
+    state
+        .candle_book
+        .lock()
+        .ok()
+        .map(|book| book.candles(query))
+}
+
+///в снимке последние history баров интервала вместе с формирующимся, затем изменения баров этого интервала;
+///у всех интервалов общий seq, поэтому в потоке подписки номера идут с пропусками
+async fn stream_candles(
+    sender: &ChannelSender,
+    state: &AppState,
+    query: &CandlesQuery,
+) -> CloseReason {
+    let mut receiver = (*state.candle_receiver).resubscribe();
+    let Some(snapshot) = candles_snapshot(state, query) else {
+        return CloseReason::ServerShutdown;
+    };
+    let mut last_seq = snapshot.seq;
+    if let Err(reason) = sender.send(&snapshot) {
+        return reason;
+    }
+    loop {
+        let sent = match receiver.recv().await {
+            Ok(encoded)
+                if encoded.message.seq > last_seq
+                    && encoded.message.data.interval == query.interval =>
+            {
+                last_seq = encoded.message.seq;
+                sender.send_encoded(&encoded)
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!("Resyncing candles after skipping {skipped} messages");
+                let Some(snapshot) = candles_snapshot(state, query) else {
+                    return CloseReason::ServerShutdown;
+                };
+                last_seq = snapshot.seq;
+                sender.send(&snapshot)
+            }
+            Err(RecvError::Closed) => return CloseReason::ServerShutdown,
+        };
+        if let Err(reason) = sent {
+            return reason;
+        }
+    }
+}
+
+///текущее значение отправляется сразу, затем только изменения; промежуточные значения медленный клиент пропускает
+async fn stream_latest<T: Serialize + Send + Sync>(
+    sender: &ChannelSender,
+    mut receiver: watch::Receiver<SharedFeed<T>>,
+) -> CloseReason {
+    loop {
+        let encoded = receiver.borrow_and_update().clone();
+        if let Err(reason) = sender.send_encoded(&encoded) {
+            return reason;
+        }
+        if receiver.changed().await.is_err() {
+            return CloseReason::ServerShutdown;
+        }
+    }
+}
+
+async fn request_open_orders(
+    state: &AppState,
+    account: &str,
+) -> Result<FeedMessage<Vec<OrderRecord>>, EngineUnavailable> {
+    let account = account.to_owned();
+    request_engine(state, |reply| EngineCommand::GetOpenOrders {
+        account,
+        reply,
+    })
+    .await
+}
+
+///приватные каналы счёта строятся из его отчётов об исполнении; seq - сквозной номер отчёта в движке,
+///поэтому номера идут с пропусками. При отставании с resync приходит снимок открытых ордеров,
+///и отчёты, уже учтённые в нём, пропускаются; без resync пропуск не восстановить, и сессия закрывается
+async fn stream_account<T: Serialize>(
+    sender: &ChannelSender,
+    state: &AppState,
+    message: impl Fn(ExecutionReport) -> Option<T> + Send,
+    resync: bool,
+) -> CloseReason {
+    let mut receiver = (*state.execution_report_receiver).resubscribe();
+    let mut last_seq = 0_u64;
+    loop {
+        let sent = match receiver.recv().await {
+            Ok(report)
+                if report.seq > last_seq
+                    && report.account.as_deref() == Some(sender.topic.as_str()) =>
+            {
+                last_seq = report.seq;
+                let Some(data) = message(report) else {
+                    continue;
+                };
+                sender.send(&FeedMessage::update(last_seq, data))
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!(
+                    "{:?} stream {} skipped {skipped} execution reports",
+                    sender.channel, sender.topic
+                );
+                if !resync {
+                    return CloseReason::Lagged;
+                }
+                let Ok(snapshot) = request_open_orders(state, &sender.topic).await else {
+                    return CloseReason::ServerShutdown;
+                };
+                last_seq = snapshot.seq;
+                sender.send(&snapshot)
+            }
+            Err(RecvError::Closed) => return CloseReason::ServerShutdown,
+        };
+        if let Err(reason) = sent {
+            return reason;
+        }
+    }
+}
+
+///без ключа сессия не открывается: ордера выставляются от имени счёта, к которому привязан ключ
+pub async fn trade(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<TradeSessionQuery>,
+) -> Response {
+    let account = match api_key_account(&state, &headers, query.api_key.as_deref()) {
+        Ok(Some(account)) => account,
+        Ok(None) => return StatusCode::UNAUTHORIZED.into_response(),
+        Err(status) => return status.into_response(),
+    };
+    let (ws, encoding) = negotiate_encoding(ws, query.encoding);
+    let cancel_on_disconnect = query.cancel_on_disconnect;
+    ws.on_upgrade(move |socket| {
+        handle_trade(socket, state, account, encoding, cancel_on_disconnect)
+    })
+}
+
+///запросы принимаются в обоих форматах: текстовый кадр - json, бинарный - msgpack
+async fn handle_trade(
+    socket: WebSocket,
+    state: AppState,
+    account: String,
+    encoding: Encoding,
+    cancel_on_disconnect: bool,
+) {
+    let mut er_receiver = (*state.execution_report_receiver).resubscribe();
+    let placed = Mutex::new(HashSet::new());
+    let (state, account, placed) = (&state, account.as_str(), &placed);
+    let session = |sender: SessionSender, mut requests: mpsc::Receiver<Message>| async move {
+        let mut last_seq = 0_u64;
+        loop {
+            let response = tokio::select! {
+                request = requests.recv() => match request {
+                    Some(frame @ (Message::Text(_) | Message::Binary(_))) => {
+                        handle_trade_request(state, account, placed, &frame).await
+                    }
+                    Some(_) => continue,
+                    None => return CloseReason::ClientClosed,
+                },
+                execution_report = er_receiver.recv() => match execution_report {
+                    Ok(report)
+                        if report.seq > last_seq && report.account.as_deref() == Some(account) =>
+                    {
+                        last_seq = report.seq;
+                        TradeResponse::ExecutionReport(report)
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Resyncing trade session {account} after skipping {skipped} reports");
+                        let Ok(snapshot) = request_open_orders(state, account).await else {
+                            return CloseReason::ServerShutdown;
+                        };
+                        last_seq = snapshot.seq;
+                        TradeResponse::OpenOrders {
+                            seq: snapshot.seq,
+                            orders: snapshot.data,
+                        }
+                    }
+                    Err(RecvError::Closed) => return CloseReason::ServerShutdown,
+                },
+            };
+
+            if let Err(reason) = send_message(&sender, encoding, &response) {
+                return reason;
+            }
+        }
+    };
+    let reason = run_session(
+        socket,
+        state.session_config,
+        &state.session_counters,
+        session,
+    )
+    .await;
+    if cancel_on_disconnect && reason.is_disconnect() {
+        let placed = placed.lock().map(|ids| ids.clone()).unwrap_or_default();
+        cancel_session_orders(state, account, placed).await;
+    }
+}
+
+///снимаются только ордера, выставленные в этой сессии; исполненные и уже снятые
+///движок отклоняет, они не считаются
+async fn cancel_session_orders(state: &AppState, account: &str, placed: HashSet<Uuid>) {
+    let mut canceled = 0;
+    for id in placed {
+        let command = |reply| EngineCommand::Cancel {
+            id,
+            account: Some(account.to_owned()),
+            reply,
+        };
+        match request_engine(state, command).await {
+            Ok(Ok(_)) => canceled += 1,
+            Ok(Err(_)) => {}
+            Err(EngineUnavailable) => {
+                eprintln!("Trade session {account} disconnected, orders were not canceled");
+                return;
+            }
+        }
+    }
+    println!("Trade session {account} disconnected, canceled {canceled} orders");
+}
+
+async fn handle_trade_request(
+    state: &AppState,
+    account: &str,
+    placed: &Mutex<HashSet<Uuid>>,
+    frame: &Message,
+) -> TradeResponse {
+    let Ok(request) = decode_message::<TradeRequest>(frame) else {
+        return TradeResponse::Reject {
+            request_id: None,
+            reason: RejectReason::InvalidMessage,
+        };
+    };
+
+    let request_id = request.request_id().to_owned();
+    let account = Some(account.to_owned());
+    let result = match request {
+        TradeRequest::Place { order, .. } => {
+            let order = OrderMessage { account, ..order };
+            let id = order.id;
+            let result = request_engine(state, |reply| EngineCommand::Place { order, reply }).await;
+            if matches!(result, Ok(Ok(_)))
+                && let Ok(mut placed) = placed.lock()
+            {
+                placed.insert(id);
+            }
+            result
+        }
+        TradeRequest::Cancel { id, .. } => {
+            request_engine(state, |reply| EngineCommand::Cancel { id, account, reply }).await
+        }
+        TradeRequest::Amend {
+            id,
+            price,
+            quantity,
+            ..
+        } => {
+            request_engine(state, |reply| EngineCommand::Amend {
+                id,
+                account,
+                price,
+                quantity,
+                reply,
+            })
+            .await
+        }
+    };
+
+    match result {
+        Ok(Ok(order)) => TradeResponse::Ack { request_id, order },
+        Ok(Err(reason)) => TradeResponse::Reject {
+            request_id: Some(request_id),
+            reason,
+        },
+        Err(EngineUnavailable) => TradeResponse::Reject {
+            request_id: Some(request_id),
+            reason: RejectReason::EngineUnavailable,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::fee_schedule::FeeSchedule;
+    use crate::session::SessionConfig;
+    use crate::{ExchangeConfig, router, start_exchange};
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use futures_util::{SinkExt, StreamExt};
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::{connect_async, tungstenite};
+    use tower::ServiceExt;
+
+    async fn serve() -> (AppState, SocketAddr) {
+        let state = start_exchange(&ExchangeConfig {
+            base_asset: "BTC",
+            quote_asset: "USDT",
+            fees: FeeSchedule::default(),
+            idempotency_window: Duration::from_mins(1),
+            idempotency_max_keys: 10,
+            //у каждого тестового счёта ключ "<счёт>-key"
+            api_keys: ["alice", "bob", "carol"]
+                .into_iter()
+                .map(|account| (format!("{account}-key"), account.to_owned()))
+                .collect(),
+            admin_key: Some(String::from("admin-key")),
+            session_config: SessionConfig {
+                heartbeat_interval: Duration::from_secs(15),
+                heartbeat_timeout: Duration::from_secs(45),
+                outbound_buffer: 16,
             },
-            Err(e) => {
-                eprintln!("Error receiving dealbook: {e:?}");
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router().with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (state, addr)
+    }
+
+    async fn open_orders(state: &AppState, account: &str) -> Vec<OrderRecord> {
+        let account = Some(account.to_owned());
+        request_engine(state, |reply| EngineCommand::GetOrders {
+            account,
+            status: Some(OrderStatus::Open),
+            reply,
+        })
+        .await
+        .unwrap()
+    }
+
+    ///выставляет ордер через торговую сессию и дожидается подтверждения
+    async fn trade_session(
+        state: &AppState,
+        addr: SocketAddr,
+        account: &str,
+        cancel_on_disconnect: bool,
+    ) -> impl Sized {
+        request_engine(state, |reply| EngineCommand::Deposit {
+            account: account.to_owned(),
+            asset: String::from("BTC"),
+            amount: 10,
+            reply,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let url = format!(
+            "ws://{addr}/api/trade?api_key={account}-key&cancel_on_disconnect={cancel_on_disconnect}"
+        );
+        let (mut socket, _) = connect_async(url).await.unwrap();
+        let place = serde_json::json!({
+            "type": "place",
+            "request_id": "1",
+            "id": Uuid::new_v4(),
+            "side": "Ask",
+            "quantity": 10,
+            "price": 500,
+        });
+        socket
+            .send(tungstenite::Message::text(place.to_string()))
+            .await
+            .unwrap();
+        while let Some(message) = socket.next().await {
+            if let tungstenite::Message::Text(text) = message.unwrap()
+                && text.contains("\"ack\"")
+            {
+                return socket;
             }
         }
+        panic!("trade session closed before the ack");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_disconnect() {
+        let (state, addr) = serve().await;
+        let alice = trade_session(&state, addr, "alice", true).await;
+        let _other_alice = trade_session(&state, addr, "alice", false).await;
+        let bob = trade_session(&state, addr, "bob", false).await;
+        assert_eq!(open_orders(&state, "alice").await.len(), 2);
+
+        drop(alice);
+        drop(bob);
+        let canceled = async {
+            while open_orders(&state, "alice").await.len() > 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), canceled)
+            .await
+            .expect("orders of the disconnected session were not canceled");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(open_orders(&state, "alice").await.len(), 1);
+        assert_eq!(open_orders(&state, "bob").await.len(), 1);
+    }
+
+    async fn subscribe_orders(addr: SocketAddr, query: &str, account: &str) -> String {
+        let (mut socket, _) = connect_async(format!("ws://{addr}/ws{query}"))
+            .await
+            .unwrap();
+        let subscribe = serde_json::json!({
+            "op": "subscribe",
+            "channel": "orders",
+            "account": account,
+        });
+        socket
+            .send(tungstenite::Message::text(subscribe.to_string()))
+            .await
+            .unwrap();
+        while let Some(message) = socket.next().await {
+            if let tungstenite::Message::Text(text) = message.unwrap() {
+                return text.to_string();
+            }
+        }
+        panic!("stream closed before the response");
+    }
+
+    #[tokio::test]
+    async fn test_private_channels_require_account_key() {
+        let (_, addr) = serve().await;
+
+        let own = subscribe_orders(addr, "?api_key=alice-key", "alice").await;
+        assert!(own.contains("\"subscribed\""), "{own}");
+        let other = subscribe_orders(addr, "?api_key=alice-key", "bob").await;
+        assert!(other.contains("\"unauthorized\""), "{other}");
+        let anonymous = subscribe_orders(addr, "", "alice").await;
+        assert!(anonymous.contains("\"unauthorized\""), "{anonymous}");
+
+        assert!(
+            connect_async(format!("ws://{addr}/ws?api_key=unknown"))
+                .await
+                .is_err()
+        );
+    }
+
+    async fn call(state: &AppState, request: Request<Body>) -> (StatusCode, String) {
+        let response = router()
+            .with_state(state.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn get(state: &AppState, api_key: &str, uri: &str) -> (StatusCode, String) {
+        let request = Request::get(uri)
+            .header(API_KEY_HEADER, api_key)
+            .body(Body::empty())
+            .unwrap();
+        call(state, request).await
+    }
+
+    async fn post_order(
+        state: &AppState,
+        api_key: &str,
+        key: &str,
+        order: &serde_json::Value,
+    ) -> (StatusCode, String) {
+        let request = Request::post("/api/orders")
+            .header("content-type", "application/json")
+            .header(API_KEY_HEADER, api_key)
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .body(Body::from(order.to_string()))
+            .unwrap();
+        call(state, request).await
+    }
+
+    #[tokio::test]
+    async fn test_rejected_order_is_not_cached() {
+        let (state, _) = serve().await;
+        let order = serde_json::json!({
+            "id": Uuid::new_v4(),
+            "account": "carol",
+            "side": "Bid",
+            "quantity": 2,
+            "price": 100,
+        });
+
+        let (status, body) = post_order(&state, "carol-key", "k1", &order).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, "\"insufficient_funds\"");
+
+        request_engine(&state, |reply| EngineCommand::Deposit {
+            account: String::from("carol"),
+            asset: String::from("USDT"),
+            amount: 200,
+            reply,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let (status, accepted) = post_order(&state, "carol-key", "k1", &order).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, replayed) = post_order(&state, "carol-key", "k1", &order).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(replayed, accepted);
+        assert_eq!(open_orders(&state, "carol").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_retries_place_once() {
+        let (state, _) = serve().await;
+        request_engine(&state, |reply| EngineCommand::Deposit {
+            account: String::from("carol"),
+            asset: String::from("USDT"),
+            amount: 1000,
+            reply,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let order = serde_json::json!({
+            "id": Uuid::new_v4(),
+            "side": "Bid",
+            "quantity": 2,
+            "price": 100,
+        });
+
+        let responses = futures_util::future::join_all(
+            (0..8).map(|_| post_order(&state, "carol-key", "k1", &order)),
+        )
+        .await;
+
+        assert!(
+            responses
+                .iter()
+                .all(|(status, _)| *status == StatusCode::CREATED)
+        );
+        assert!(responses.iter().all(|(_, ack)| *ack == responses[0].1));
+        assert_eq!(open_orders(&state, "carol").await.len(), 1);
+    }
+
+    async fn post_deposit(state: &AppState, admin_key: Option<&str>) -> StatusCode {
+        let mut request =
+            Request::post("/api/accounts/dave/deposits").header("content-type", "application/json");
+        if let Some(admin_key) = admin_key {
+            request = request.header(ADMIN_KEY_HEADER, admin_key);
+        }
+        let body = serde_json::json!({"asset": "USDT", "amount": 100});
+        router()
+            .with_state(state.clone())
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_deposit_requires_admin_key() {
+        let (state, _) = serve().await;
+
+        assert_eq!(post_deposit(&state, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            post_deposit(&state, Some("alice-key")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            post_deposit(&state, Some("admin-key")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trade_session_requires_api_key() {
+        let (_, addr) = serve().await;
+
+        for query in ["", "?api_key=unknown", "?account=alice"] {
+            assert!(
+                connect_async(format!("ws://{addr}/api/trade{query}"))
+                    .await
+                    .is_err(),
+                "{query}"
+            );
+        }
+        assert!(
+            connect_async(format!("ws://{addr}/api/trade?api_key=alice-key"))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trade_session_in_message_pack() {
+        let (_, addr) = serve().await;
+        let url = format!("ws://{addr}/api/trade?api_key=alice-key&encoding=msgpack");
+        let (mut socket, _) = connect_async(url).await.unwrap();
+        let cancel = serde_json::json!({
+            "type": "cancel",
+            "request_id": "1",
+            "id": Uuid::new_v4(),
+        });
+        let frame = rmp_serde::to_vec_named(&cancel).unwrap();
+        socket
+            .send(tungstenite::Message::binary(frame))
+            .await
+            .unwrap();
+
+        while let Some(message) = socket.next().await {
+            if let tungstenite::Message::Binary(bytes) = message.unwrap() {
+                let response: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+                assert_eq!(response["type"], "reject");
+                assert_eq!(response["request_id"], "1");
+                assert_eq!(response["reason"], "unknown_order");
+                return;
+            }
+        }
+        panic!("trade session closed before the response");
+    }
+
+    #[tokio::test]
+    async fn test_rest_orders_are_scoped_to_the_api_key() {
+        let (state, _) = serve().await;
+        request_engine(&state, |reply| EngineCommand::Deposit {
+            account: String::from("alice"),
+            asset: String::from("BTC"),
+            amount: 10,
+            reply,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let id = Uuid::new_v4();
+        let order = serde_json::json!({
+            "id": id,
+            "side": "Ask",
+            "quantity": 1,
+            "price": 500,
+        });
+
+        assert_eq!(
+            post_order(&state, "unknown", "k1", &order).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        let for_alice = serde_json::json!({"account": "alice", "id": Uuid::new_v4(), "side": "Ask", "quantity": 1, "price": 500});
+        assert_eq!(
+            post_order(&state, "bob-key", "k1", &for_alice).await.0,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            post_order(&state, "alice-key", "k1", &order).await.0,
+            StatusCode::CREATED
+        );
+
+        let (_, orders) = get(&state, "alice-key", "/api/orders").await;
+        assert!(orders.contains(&id.to_string()));
+        assert_eq!(get(&state, "bob-key", "/api/orders").await.1, "[]");
+        assert_eq!(
+            get(&state, "bob-key", "/api/orders?account=alice").await.0,
+            StatusCode::FORBIDDEN
+        );
+        let uri = format!("/api/orders/{id}");
+        assert_eq!(get(&state, "alice-key", &uri).await.0, StatusCode::OK);
+        assert_eq!(get(&state, "bob-key", &uri).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(
+            get(&state, "bob-key", "/api/accounts/alice").await.0,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            get(&state, "alice-key", "/api/accounts/alice").await.0,
+            StatusCode::OK
+        );
+    }
+
+    async fn delete_orders(state: &AppState, header: (&str, &str), query: &str) -> StatusCode {
+        let request = Request::delete(format!("/api/orders{query}"))
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap();
+        call(state, request).await.0
+    }
+
+    #[tokio::test]
+    async fn test_mass_cancel_is_limited_to_own_orders() {
+        let (state, addr) = serve().await;
+        let _alice = trade_session(&state, addr, "alice", false).await;
+        let _bob = trade_session(&state, addr, "bob", false).await;
+
+        assert_eq!(
+            delete_orders(&state, (API_KEY_HEADER, "unknown"), "").await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            delete_orders(&state, (API_KEY_HEADER, "bob-key"), "?account=alice").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            delete_orders(&state, (API_KEY_HEADER, "bob-key"), "").await,
+            StatusCode::OK
+        );
+        assert_eq!(open_orders(&state, "alice").await.len(), 1);
+        assert!(open_orders(&state, "bob").await.is_empty());
+
+        assert_eq!(
+            delete_orders(&state, (ADMIN_KEY_HEADER, "admin-key"), "").await,
+            StatusCode::OK
+        );
+        assert!(open_orders(&state, "alice").await.is_empty());
+    }
+
+    ///следующее событие SSE из ответа с chunked-кодированием: строки размеров чанков пропускаются
+    async fn next_event(stream: &mut TcpStream, buffer: &mut String) -> serde_json::Value {
+        loop {
+            while let Some(end) = buffer.find('\n') {
+                let line: String = buffer.drain(..=end).collect();
+                if let Some(data) = line.trim_end().strip_prefix("data: ") {
+                    return serde_json::from_str(data).unwrap();
+                }
+            }
+            let mut chunk = [0; 4096];
+            let read = stream.read(&mut chunk).await.unwrap();
+            assert!(read > 0, "event stream closed");
+            buffer.push_str(std::str::from_utf8(&chunk[..read]).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_sends_snapshot_then_updates() {
+        let (state, addr) = serve().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /api/stream/depth HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buffer = String::new();
+        let events = async {
+            let snapshot = next_event(&mut stream, &mut buffer).await;
+            let _alice = trade_session(&state, addr, "alice", false).await;
+            let update = next_event(&mut stream, &mut buffer).await;
+            (snapshot, update)
+        };
+        let (snapshot, update) = tokio::time::timeout(Duration::from_secs(5), events)
+            .await
+            .expect("event stream stalled");
+
+        assert_eq!(snapshot["channel"], "depth");
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(update["type"], "update");
+        assert_eq!(update["data"][0]["price"], 500);
+        assert!(update["seq"].as_u64() > snapshot["seq"].as_u64());
+    }
+
+    #[tokio::test]
+    async fn test_conflated_depth_sends_latest_state_once_per_interval() {
+        const CONFLATION: Duration = Duration::from_millis(200);
+        const ORDERS: u32 = 8;
+        let (state, addr) = serve().await;
+        request_engine(&state, |reply| EngineCommand::Deposit {
+            account: String::from("alice"),
+            asset: String::from("BTC"),
+            amount: 100,
+            reply,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let (mut depth, _) = connect_async(format!("ws://{addr}/ws")).await.unwrap();
+        let subscribe = serde_json::json!({
+            "op": "subscribe",
+            "channel": "depth",
+            "instrument": "BTCUSDT",
+            "conflation_ms": CONFLATION.as_millis(),
+        });
+        depth
+            .send(tungstenite::Message::text(subscribe.to_string()))
+            .await
+            .unwrap();
+        let mut next_frame = async || loop {
+            if let tungstenite::Message::Text(text) = depth.next().await.unwrap().unwrap() {
+                let frame: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if frame.get("seq").is_some() {
+                    return (tokio::time::Instant::now(), frame);
+                }
+            }
+        };
+        next_frame().await;
+
+        //ордера приходят чаще интервала конфляции
+        let (mut trade, _) = connect_async(format!("ws://{addr}/api/trade?api_key=alice-key"))
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            for request_id in 1..=ORDERS {
+                let place = serde_json::json!({
+                    "type": "place",
+                    "request_id": request_id.to_string(),
+                    "id": Uuid::new_v4(),
+                    "side": "Ask",
+                    "quantity": 1,
+                    "price": 500,
+                });
+                trade
+                    .send(tungstenite::Message::text(place.to_string()))
+                    .await
+                    .unwrap();
+                tokio::time::sleep(CONFLATION / 4).await;
+            }
+            trade
+        });
+        let mut updates = Vec::new();
+        let collected = async {
+            loop {
+                let frame = next_frame().await;
+                let done = frame.1["data"]["ask"][0]["quantity"] == ORDERS;
+                updates.push(frame);
+                if done {
+                    break;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), collected)
+            .await
+            .expect("conflated depth never reached the latest state");
+        assert!(
+            tokio::time::timeout(CONFLATION * 2, next_frame())
+                .await
+                .is_err(),
+            "unchanged depth was sent again"
+        );
+
+        assert!(
+            updates.len() < 5,
+            "{} frames for {ORDERS} orders",
+            updates.len()
+        );
+        for pair in updates.windows(2) {
+            assert!(pair[1].0 - pair[0].0 >= CONFLATION * 9 / 10);
+        }
+        let (_, latest) = updates.last().unwrap();
+        assert_eq!(latest["type"], "snapshot");
+        assert_eq!(latest["data"]["ask"][0]["orders"], ORDERS);
     }
 }
@@ -0,0 +1,270 @@
+use crate::matching::models::order_ack::OrderAck;
+use crate::matching::models::order_message::OrderMessage;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+///ключ идемпотентности действует в пределах счёта
+type CacheKey = (String, String);
+
+pub enum IdempotencyLookup {
+    ///ключ занят этим запросом: отправка ack в sender завершает запись,
+    ///sender, сброшенный без ack, освобождает ключ
+    New(watch::Sender<Option<OrderAck>>),
+    ///тот же ордер ещё выставляется другим запросом
+    Pending(watch::Receiver<Option<OrderAck>>),
+    Replay(OrderAck),
+    Conflict,
+}
+
+struct IdempotencyEntry {
+    message: OrderMessage,
+    ack: watch::Receiver<Option<OrderAck>>,
+    expires_at: Instant,
+}
+
+///ключи живут window; при переполнении вытесняются самые старые, не дожидаясь окна
+pub struct IdempotencyCache {
+    window: Duration,
+    max_entries: usize,
+    entries: HashMap<CacheKey, IdempotencyEntry>,
+    expirations: VecDeque<(Instant, CacheKey)>, //ключи в порядке добавления, для очистки по окну
+}
+
+impl IdempotencyCache {
+    pub fn new(window: Duration, max_entries: usize) -> Self {
+        Self {
+            window,
+            max_entries,
+            entries: HashMap::new(),
+            expirations: VecDeque::new(),
+        }
+    }
+
+    ///при New ключ сразу занимается ожидающей записью, чтобы параллельный повтор
+    ///получил Pending, а не выставил ордер второй раз
+    pub fn lookup(
+        &mut self,
+        account: &str,
+        key: &str,
+        message: &OrderMessage,
+        now: Instant,
+    ) -> IdempotencyLookup {
+        self.evict_expired(now);
+
+        let cache_key = (account.to_owned(), key.to_owned());
+        if let Some(entry) = self.entries.get(&cache_key) {
+            let ack = entry.ack.borrow().clone();
+            let placing = entry.ack.has_changed().is_ok();
+            if ack.is_some() || placing {
+                if entry.message != *message {
+                    return IdempotencyLookup::Conflict;
+                }
+                return ack.map_or_else(
+                    || IdempotencyLookup::Pending(entry.ack.clone()),
+                    IdempotencyLookup::Replay,
+                );
+            }
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        self.insert(cache_key, message.clone(), receiver, now);
+        IdempotencyLookup::New(sender)
+    }
+
+    fn insert(
+        &mut self,
+        key: CacheKey,
+        message: OrderMessage,
+        ack: watch::Receiver<Option<OrderAck>>,
+        now: Instant,
+    ) {
+        self.entries.remove(&key);
+        while self.entries.len() >= self.max_entries
+            && let Some((expires_at, oldest)) = self.expirations.pop_front()
+        {
+            self.remove(&oldest, expires_at);
+        }
+        let expires_at = now + self.window;
+        self.expirations.push_back((expires_at, key.clone()));
+        self.entries.insert(
+            key,
+            IdempotencyEntry {
+                message,
+                ack,
+                expires_at,
+            },
+        );
+    }
+
+    fn evict_expired(&mut self, now: Instant) {
+        while let Some((expires_at, _)) = self.expirations.front() {
+            if *expires_at > now {
+                break;
+            }
+            if let Some((expires_at, key)) = self.expirations.pop_front() {
+                self.remove(&key, expires_at);
+            }
+        }
+    }
+
+    ///в очереди могут остаться сроки заменённых записей - они не трогают новую запись с тем же ключом
+    fn remove(&mut self, key: &CacheKey, expires_at: Instant) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.expires_at == expires_at)
+        {
+            self.entries.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_side::OrderSide;
+    use uuid::Uuid;
+
+    fn order_message(quantity: u32) -> OrderMessage {
+        OrderMessage {
+            id: Uuid::new_v4(),
+            account: None,
+            side: OrderSide::Bid,
+            quantity,
+            price: 500,
+        }
+    }
+
+    fn insert(cache: &mut IdempotencyCache, key: &str, message: &OrderMessage, now: Instant) {
+        let IdempotencyLookup::New(sender) = cache.lookup("alice", key, message, now) else {
+            panic!("expected a new key");
+        };
+        sender.send_replace(Some(OrderAck::new(message.id)));
+    }
+
+    #[test]
+    fn test_lookup_unknown_key() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let message = order_message(10);
+
+        assert!(matches!(
+            cache.lookup("alice", "key", &message, Instant::now()),
+            IdempotencyLookup::New(_)
+        ));
+    }
+
+    #[test]
+    fn test_lookup_replays_original_ack() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let now = Instant::now();
+        let message = order_message(10);
+
+        insert(&mut cache, "key", &message, now);
+
+        match cache.lookup("alice", "key", &message, now + Duration::from_secs(30)) {
+            IdempotencyLookup::Replay(replayed) => assert_eq!(replayed.id, message.id),
+            _ => panic!("expected replay of the original ack"),
+        }
+    }
+
+    #[test]
+    fn test_lookup_conflicting_message() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let now = Instant::now();
+
+        insert(&mut cache, "key", &order_message(10), now);
+
+        assert!(matches!(
+            cache.lookup("alice", "key", &order_message(20), now),
+            IdempotencyLookup::Conflict
+        ));
+    }
+
+    #[test]
+    fn test_keys_are_scoped_per_account() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let now = Instant::now();
+
+        insert(&mut cache, "key", &order_message(10), now);
+
+        assert!(matches!(
+            cache.lookup("bob", "key", &order_message(20), now),
+            IdempotencyLookup::New(_)
+        ));
+    }
+
+    #[test]
+    fn test_lookup_while_placing_waits_for_ack() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let now = Instant::now();
+        let message = order_message(10);
+
+        let IdempotencyLookup::New(sender) = cache.lookup("alice", "key", &message, now) else {
+            panic!("expected a new key");
+        };
+        let IdempotencyLookup::Pending(receiver) = cache.lookup("alice", "key", &message, now)
+        else {
+            panic!("expected a pending key");
+        };
+        let ack = OrderAck::new(message.id);
+        sender.send_replace(Some(ack.clone()));
+
+        assert_eq!(*receiver.borrow(), Some(ack));
+        assert!(matches!(
+            cache.lookup("alice", "key", &message, now),
+            IdempotencyLookup::Replay(_)
+        ));
+    }
+
+    #[test]
+    fn test_dropped_sender_frees_key() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let now = Instant::now();
+
+        drop(cache.lookup("alice", "key", &order_message(10), now));
+
+        assert!(matches!(
+            cache.lookup("alice", "key", &order_message(20), now),
+            IdempotencyLookup::New(_)
+        ));
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_lookup_after_window_expired() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 10);
+        let now = Instant::now();
+        let message = order_message(10);
+
+        insert(&mut cache, "key", &message, now);
+
+        assert!(matches!(
+            cache.lookup("alice", "key", &message, now + Duration::from_mins(1)),
+            IdempotencyLookup::New(_)
+        ));
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.expirations.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_evicts_oldest_when_full() {
+        let mut cache = IdempotencyCache::new(Duration::from_mins(1), 2);
+        let now = Instant::now();
+        let messages: Vec<OrderMessage> = (1..=3).map(order_message).collect();
+
+        for (key, message) in ["first", "second", "third"].into_iter().zip(&messages) {
+            insert(&mut cache, key, message, now);
+        }
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(matches!(
+            cache.lookup("alice", "first", &messages[0], now),
+            IdempotencyLookup::New(_)
+        ));
+        assert!(matches!(
+            cache.lookup("alice", "third", &messages[2], now),
+            IdempotencyLookup::Replay(_)
+        ));
+    }
+}
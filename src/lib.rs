@@ -0,0 +1,6 @@
+//! модели сообщений и кодек протокола: клиенты разбирают ими кадры /ws, /api/trade и ответы REST.
+//! Кодек не зависит от websocket-библиотеки, состояние движка остаётся в бинарнике
+pub mod codec;
+pub mod matching {
+    pub mod models;
+}
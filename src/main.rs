@@ -1,54 +1,239 @@
+mod frame;
 mod handlers;
+mod idempotency;
 mod matching;
+mod session;
+use crate::frame::{EncodedFeed, SharedFeed};
+use crate::matching::models::account_book::AccountBook;
+use crate::matching::models::best_bid_offer::BestBidOffer;
+use crate::matching::models::candle::{Candle, CandleBook};
 use crate::matching::models::dealbook::DealBook;
-use crate::matching::models::depth_of_market::DepthOfMarket;
-use crate::matching::models::order_message::OrderMessage;
+use crate::matching::models::depth_of_market::DepthChange;
+use crate::matching::models::engine_command::EngineCommand;
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::fee_schedule::FeeSchedule;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::market_by_order::OrderEvent;
+use crate::matching::models::order_store::OrderStore;
+use crate::matching::models::subscription_message::Channel;
+use crate::matching::models::ticker::Ticker;
+use crate::matching::models::trade_feed::PublicTrade;
+use crate::matching::models::trade_history::TradeHistory;
 use axum::{Router, routing::any, routing::get, routing::post};
-use handlers::{create_order, get_deals, get_orderbook, healthcheck};
+use handlers::{
+    cancel_orders, create_order, deposit, get_balances, get_candles, get_event_stream,
+    get_market_by_order_snapshot, get_order, get_orderbook_snapshot, get_orders, get_sessions,
+    get_streams, get_ticker, get_trades, healthcheck, trade,
+};
+use idempotency::IdempotencyCache;
+use matching::candles::aggregate_candles;
 use matching::engine::matching_engine;
+use matching::send::Publisher;
+use matching::ticker::aggregate_ticker;
+use matching_be::codec;
+use session::{SessionConfig, SessionCounters};
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio::task::spawn_blocking;
 
 #[derive(Clone)]
 pub struct AppState {
-    order_message_sender: Arc<broadcast::Sender<OrderMessage>>,
-    orderbook_receiver: Arc<broadcast::Receiver<DepthOfMarket>>,
-    dealbook_receiver: Arc<broadcast::Receiver<DealBook>>,
+    symbol: String,
+    engine_command_sender: Arc<mpsc::Sender<EngineCommand>>,
+    orderbook_receiver: Arc<broadcast::Receiver<SharedFeed<Vec<DepthChange>>>>,
+    market_by_order_receiver: Arc<broadcast::Receiver<SharedFeed<Vec<OrderEvent>>>>,
+    dealbook_receiver: Arc<broadcast::Receiver<SharedFeed<Vec<PublicTrade>>>>,
+    dealbook_snapshot: watch::Receiver<FeedMessage<Vec<PublicTrade>>>,
+    best_bid_offer_receiver: watch::Receiver<SharedFeed<BestBidOffer>>,
+    candle_receiver: Arc<broadcast::Receiver<SharedFeed<Candle>>>,
+    candle_book: Arc<Mutex<CandleBook>>,
+    ticker_receiver: watch::Receiver<SharedFeed<Ticker>>,
+    execution_report_receiver: Arc<broadcast::Receiver<ExecutionReport>>,
+    idempotency_cache: Arc<Mutex<IdempotencyCache>>,
+    api_keys: Arc<HashMap<String, String>>,
+    admin_key: Option<Arc<str>>,
+    session_config: SessionConfig,
+    session_counters: Arc<SessionCounters>,
+}
+
+///параметры, с которыми запускаются движок и сервисы рыночных данных
+struct ExchangeConfig {
+    base_asset: &'static str,
+    quote_asset: &'static str,
+    fees: FeeSchedule,
+    idempotency_window: Duration,
+    idempotency_max_keys: usize,
+    api_keys: HashMap<String, String>, //ключ доступа -> счёт
+    admin_key: Option<String>,         //без него служебные методы недоступны
+    session_config: SessionConfig,
 }
 
 #[forbid(unsafe_code)]
 #[tokio::main]
 async fn main() {
     let port = 28103_u16;
-    let addr_size = 1000_usize;
+    let config = ExchangeConfig {
+        base_asset: "BTC",
+        quote_asset: "USDT",
+        fees: FeeSchedule {
+            maker_bps: env_or("MAKER_FEE_BPS", 0),
+            taker_bps: env_or("TAKER_FEE_BPS", 0),
+        },
+        idempotency_window: Duration::from_secs(env_or("IDEMPOTENCY_WINDOW_SECS", 24 * 60 * 60)),
+        idempotency_max_keys: env_or("IDEMPOTENCY_MAX_KEYS", 100_000),
+        api_keys: std::env::var("API_KEYS")
+            .map(|keys| parse_api_keys(&keys))
+            .unwrap_or_default(),
+        admin_key: std::env::var("ADMIN_KEY").ok(),
+        session_config: SessionConfig {
+            heartbeat_interval: Duration::from_secs(15),
+            heartbeat_timeout: Duration::from_secs(45),
+            outbound_buffer: 256,
+        },
+    };
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
 
-    let (om_sender, _) = broadcast::channel(addr_size);
+    let app = router().with_state(start_exchange(&config));
+
+    axum::serve(listener, app).await.unwrap();
+}
+
+///запускает движок и сервисы рыночных данных, возвращает состояние для обработчиков
+fn start_exchange(config: &ExchangeConfig) -> AppState {
+    let addr_size = 1000_usize;
+    let trade_history_size = 100_000_usize;
+    let finished_orders_size = 100_000_usize;
+    let candle_history_size = 1000_usize;
+    let symbol = format!("{}{}", config.base_asset, config.quote_asset);
+
+    let (command_sender, mut command_receiver) = mpsc::channel::<EngineCommand>(addr_size);
     let (dom_sender, _) = broadcast::channel(addr_size);
+    let (mbo_sender, _) = broadcast::channel(addr_size);
     let (db_sender, _) = broadcast::channel(addr_size);
+    let (er_sender, _) = broadcast::channel(addr_size);
+    let (candle_sender, _) = broadcast::channel(addr_size);
+    let (db_snapshot_sender, db_snapshot_receiver) =
+        watch::channel(FeedMessage::snapshot(0, Vec::new()));
+    let bbo_snapshot = FeedMessage::snapshot(0, BestBidOffer::default());
+    let (bbo_sender, bbo_receiver) = watch::channel(Arc::new(
+        EncodedFeed::new(Channel::Bbo, &symbol, bbo_snapshot).unwrap(),
+    ));
+    let ticker_snapshot = FeedMessage::snapshot(0, Ticker::default());
+    let (ticker_sender, ticker_receiver) = watch::channel(Arc::new(
+        EncodedFeed::new(Channel::Ticker, &symbol, ticker_snapshot).unwrap(),
+    ));
 
-    let mut om_receiver: broadcast::Receiver<OrderMessage> = om_sender.subscribe();
-    let dom_receiver: broadcast::Receiver<DepthOfMarket> = dom_sender.subscribe();
-    let db_receiver: broadcast::Receiver<DealBook> = db_sender.subscribe();
+    let dom_receiver: broadcast::Receiver<SharedFeed<Vec<DepthChange>>> = dom_sender.subscribe();
+    let mbo_receiver: broadcast::Receiver<SharedFeed<Vec<OrderEvent>>> = mbo_sender.subscribe();
+    let db_receiver: broadcast::Receiver<SharedFeed<Vec<PublicTrade>>> = db_sender.subscribe();
+    let er_receiver: broadcast::Receiver<ExecutionReport> = er_sender.subscribe();
+    let candle_receiver: broadcast::Receiver<SharedFeed<Candle>> = candle_sender.subscribe();
+    let candle_book = Arc::new(Mutex::new(CandleBook::new(candle_history_size)));
+    tokio::spawn(aggregate_candles(
+        symbol.clone(),
+        command_sender.clone(),
+        db_sender.subscribe(),
+        candle_book.clone(),
+        candle_sender,
+    ));
+    tokio::spawn(aggregate_ticker(
+        symbol.clone(),
+        command_sender.clone(),
+        db_sender.subscribe(),
+        ticker_sender,
+    ));
 
     let state: AppState = AppState {
-        order_message_sender: Arc::new(om_sender),
+        symbol: symbol.clone(),
+        engine_command_sender: Arc::new(command_sender),
         orderbook_receiver: Arc::new(dom_receiver),
+        market_by_order_receiver: Arc::new(mbo_receiver),
         dealbook_receiver: Arc::new(db_receiver),
+        dealbook_snapshot: db_snapshot_receiver,
+        best_bid_offer_receiver: bbo_receiver,
+        candle_receiver: Arc::new(candle_receiver),
+        candle_book,
+        ticker_receiver,
+        execution_report_receiver: Arc::new(er_receiver),
+        idempotency_cache: Arc::new(Mutex::new(IdempotencyCache::new(
+            config.idempotency_window,
+            config.idempotency_max_keys,
+        ))),
+        api_keys: Arc::new(config.api_keys.clone()),
+        admin_key: config.admin_key.as_deref().map(Arc::from),
+        session_config: config.session_config,
+        session_counters: Arc::new(SessionCounters::default()),
     };
 
-    spawn_blocking(move || matching_engine(&mut om_receiver, &dom_sender, &db_sender));
+    let mut publisher = Publisher::new(
+        &symbol,
+        dom_sender,
+        mbo_sender,
+        db_sender,
+        db_snapshot_sender,
+        bbo_sender,
+        er_sender,
+    );
 
-    let app = Router::new()
-        .route("/api/orderbook", any(get_orderbook))
-        .route("/api/dealbook", any(get_deals))
-        .route("/api/orders", post(create_order))
-        .route("/api/health", get(healthcheck))
-        .with_state(state);
+    let order_store = OrderStore::new(
+        AccountBook::new(config.base_asset, config.quote_asset, config.fees),
+        finished_orders_size,
+    );
+    let trade_history = TradeHistory::new(trade_history_size);
+    spawn_blocking(move || {
+        matching_engine(
+            &symbol,
+            order_store,
+            trade_history,
+            &mut command_receiver,
+            &mut publisher,
+        );
+    });
 
-    axum::serve(listener, app).await.unwrap();
+    state
+}
+
+///`API_KEYS` задаётся парами "ключ:счёт" через запятую
+fn parse_api_keys(keys: &str) -> HashMap<String, String> {
+    keys.split(',')
+        .filter_map(|pair| pair.split_once(':'))
+        .map(|(key, account)| (key.trim().to_owned(), account.trim().to_owned()))
+        .collect()
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn router() -> Router<AppState> {
+    Router::new()
+        .route("/ws", any(get_streams))
+        .route("/api/stream/{channel}", get(get_event_stream))
+        .route("/api/orderbook/snapshot", get(get_orderbook_snapshot))
+        .route(
+            "/api/orderbook/l3/snapshot",
+            get(get_market_by_order_snapshot),
+        )
+        .route(
+            "/api/orders",
+            post(create_order).get(get_orders).delete(cancel_orders),
+        )
+        .route("/api/orders/{id}", get(get_order))
+        .route("/api/accounts/{account}", get(get_balances))
+        .route("/api/accounts/{account}/deposits", post(deposit))
+        .route("/api/trades", get(get_trades))
+        .route("/api/candles", get(get_candles))
+        .route("/api/ticker", get(get_ticker))
+        .route("/api/trade", any(trade))
+        .route("/api/sessions", get(get_sessions))
+        .route("/api/health", get(healthcheck))
 }
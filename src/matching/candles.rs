@@ -0,0 +1,69 @@
+use crate::frame::{EncodedFeed, SharedFeed};
+use crate::matching::models::candle::{Candle, CandleBook};
+use crate::matching::models::engine_command::EngineCommand;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::subscription_message::Channel;
+use crate::matching::models::trade_feed::PublicTrade;
+use crate::matching::replay::missed_trades;
+use chrono::{TimeDelta, Utc};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{MissedTickBehavior, interval};
+
+const CLOSE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+///запас на сделки, которые движок отметил до границы бара, а лента доставила после неё
+const CLOSE_DELAY: TimeDelta = TimeDelta::milliseconds(200);
+
+///строит бары по ленте сделок и рассылает их изменения; бары без новых сделок закрываются по таймеру.
+///При отставании от ленты пропущенные сделки берутся из истории движка и бары достраиваются
+pub async fn aggregate_candles(
+    symbol: String,
+    engine: mpsc::Sender<EngineCommand>,
+    mut trades: broadcast::Receiver<SharedFeed<Vec<PublicTrade>>>,
+    book: Arc<Mutex<CandleBook>>,
+    sender: broadcast::Sender<SharedFeed<Candle>>,
+) {
+    let mut ticker = interval(CLOSE_CHECK_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let events = tokio::select! {
+            received = trades.recv() => match received {
+                Ok(encoded) => lock(&book).apply(&encoded.message.data),
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Candles skipped {skipped} trade messages, restoring from trade history");
+                    let after = lock(&book).last_trade_id();
+                    let Some(missed) = missed_trades(&engine, after).await else {
+                        return;
+                    };
+                    lock(&book).restore(&missed)
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = ticker.tick() => lock(&book).close_expired(Utc::now() - CLOSE_DELAY),
+        };
+        for event in events {
+            send_candle(&symbol, &sender, event);
+        }
+    }
+}
+
+fn lock(book: &Mutex<CandleBook>) -> MutexGuard<'_, CandleBook> {
+    book.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn send_candle(
+    symbol: &str,
+    sender: &broadcast::Sender<SharedFeed<Candle>>,
+    event: FeedMessage<Candle>,
+) {
+    let topic = format!("{symbol}:{}", event.data.interval.as_str());
+    match EncodedFeed::new(Channel::Candles, &topic, event) {
+        //подписчиков может не быть, это не ошибка
+        Ok(encoded) => {
+            let _ = sender.send(Arc::new(encoded));
+        }
+        Err(e) => eprintln!("Error serializing candle to JSON: {e:?}"),
+    }
+}
@@ -1,48 +1,313 @@
 use crate::matching::models::ask_order::AskOrder;
 use crate::matching::models::bid_order::BidOrder;
+use crate::matching::models::cancel_filter::CancelFilter;
+use crate::matching::models::deal::Deal;
 use crate::matching::models::dealbook::DealBook;
-use crate::matching::models::depth_of_market::DepthOfMarket;
+use crate::matching::models::engine_command::{EngineCommand, OrderReply};
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::mass_cancel_report::MassCancelReport;
 use crate::matching::models::order_message::OrderMessage;
+use crate::matching::models::order_record::OrderRecord;
 use crate::matching::models::order_side::OrderSide;
+use crate::matching::models::order_status::OrderStatus;
+use crate::matching::models::order_store::OrderStore;
 use crate::matching::models::orderbook::OrderBook;
-use crate::matching::send::send_data;
-use tokio::sync::broadcast;
+use crate::matching::models::reject_reason::RejectReason;
+use crate::matching::models::trade_feed::PublicTrade;
+use crate::matching::models::trade_history::TradeHistory;
+use crate::matching::send::Publisher;
+use chrono::Utc;
+use std::collections::HashSet;
+use tokio::sync::mpsc;
+use uuid::Uuid;
 
 pub fn matching_engine(
-    om_receiver: &mut broadcast::Receiver<OrderMessage>,
-    dom_sender: &broadcast::Sender<DepthOfMarket>,
-    db_sender: &broadcast::Sender<DealBook>,
+    symbol: &str,
+    mut order_store: OrderStore,
+    mut trade_history: TradeHistory,
+    command_receiver: &mut mpsc::Receiver<EngineCommand>,
+    publisher: &mut Publisher,
 ) {
     let mut orderbook: OrderBook = OrderBook::new();
-    while let Ok(order_message) = om_receiver.blocking_recv() {
-        let mut dealbook: DealBook = DealBook::new();
-        matching_orders(&order_message, &mut orderbook, &mut dealbook);
-        send_data(dom_sender, orderbook.get_dom(), db_sender, dealbook);
+    let mut last_deal: Option<Deal> = None;
+    let mut next_trade_id: u64 = 1;
+    let mut next_match_id: u64 = 1;
+    while let Some(command) = command_receiver.blocking_recv() {
+        let mut dealbook: DealBook = DealBook::new(next_trade_id, next_match_id, Utc::now());
+        let execution_reports: Option<Vec<ExecutionReport>> = match command {
+            EngineCommand::Place { order, reply } => {
+                let result = place_order(&order, &mut orderbook, &mut order_store, &mut dealbook);
+                let execution_reports = result.clone().ok();
+                send_order_reply(reply, &order_store, order.id, result);
+                execution_reports
+            }
+            EngineCommand::Cancel { id, account, reply } => {
+                let result = cancel_order(id, account.as_deref(), &mut orderbook, &mut order_store);
+                let execution_reports = result.clone().ok().map(|report| vec![report]);
+                send_order_reply(reply, &order_store, id, result);
+                execution_reports
+            }
+            EngineCommand::Amend {
+                id,
+                account,
+                price,
+                quantity,
+                reply,
+            } => {
+                let result = amend_order(
+                    id,
+                    account.as_deref(),
+                    price,
+                    quantity,
+                    &mut orderbook,
+                    &mut order_store,
+                    &mut dealbook,
+                );
+                let execution_reports = result.clone().ok();
+                send_order_reply(reply, &order_store, id, result);
+                execution_reports
+            }
+            EngineCommand::MassCancel { filter, reply } => {
+                let (report, execution_reports) =
+                    mass_cancel(symbol, &filter, &mut orderbook, &mut order_store);
+                let _ = reply.send(report);
+                Some(execution_reports)
+            }
+            command => {
+                answer_request(
+                    command,
+                    &orderbook,
+                    &mut order_store,
+                    &trade_history,
+                    publisher,
+                );
+                None
+            }
+        };
+
+        next_trade_id = dealbook.next_id();
+        if !dealbook.deals.is_empty() {
+            next_match_id += 1;
+        }
+        if let Some(execution_reports) = execution_reports {
+            publish(
+                publisher,
+                &mut orderbook,
+                &mut trade_history,
+                &mut last_deal,
+                dealbook,
+                execution_reports,
+            );
+        }
+    }
+}
+
+fn publish(
+    publisher: &mut Publisher,
+    orderbook: &mut OrderBook,
+    trade_history: &mut TradeHistory,
+    last_deal: &mut Option<Deal>,
+    dealbook: DealBook,
+    execution_reports: Vec<ExecutionReport>,
+) {
+    if let Some(deal) = dealbook.deals.last() {
+        *last_deal = Some(deal.clone());
+    }
+    trade_history.extend(dealbook.deals.iter().map(PublicTrade::from));
+    let best_bid_offer = orderbook.get_best_bid_offer(last_deal.as_ref());
+    let depth_changes = orderbook.take_depth_changes();
+    //без изменений уровней обновление стакана не публикуется, и сумма не нужна
+    let depth_checksum = if depth_changes.is_empty() {
+        0
+    } else {
+        orderbook.get_checksum()
+    };
+    publisher.send_data(
+        depth_changes,
+        depth_checksum,
+        orderbook.take_order_events(),
+        dealbook,
+        best_bid_offer,
+        execution_reports,
+    );
+}
+
+///запросы, которые не меняют книгу: ответ уходит сразу, рыночные данные не публикуются
+fn answer_request(
+    command: EngineCommand,
+    orderbook: &OrderBook,
+    order_store: &mut OrderStore,
+    trade_history: &TradeHistory,
+    publisher: &Publisher,
+) {
+    match command {
+        EngineCommand::GetOrder { id, reply } => {
+            let _ = reply.send(order_store.get(&id).cloned());
+        }
+        EngineCommand::GetOrders {
+            account,
+            status,
+            reply,
+        } => {
+            let _ = reply.send(order_store.find(account.as_deref(), status));
+        }
+        EngineCommand::GetOpenOrders { account, reply } => {
+            let _ = reply.send(FeedMessage::snapshot(
+                publisher.execution_report_seq(),
+                order_store.find(Some(&account), Some(OrderStatus::Open)),
+            ));
+        }
+        EngineCommand::GetDepth { reply } => {
+            let _ = reply.send(
+                FeedMessage::snapshot(publisher.depth_seq(), orderbook.get_dom())
+                    .with_checksum(orderbook.get_checksum()),
+            );
+        }
+        EngineCommand::GetMarketByOrder { reply } => {
+            let _ = reply.send(FeedMessage::snapshot(
+                publisher.market_by_order_seq(),
+                orderbook.get_market_by_order(),
+            ));
+        }
+        EngineCommand::Deposit {
+            account,
+            asset,
+            amount,
+            reply,
+        } => {
+            let _ = reply.send(order_store.accounts.deposit(&account, &asset, amount));
+        }
+        EngineCommand::GetBalances { account, reply } => {
+            let _ = reply.send(order_store.accounts.balances(&account));
+        }
+        EngineCommand::GetTrades { query, reply } => {
+            let _ = reply.send(trade_history.page(&query));
+        }
+        EngineCommand::Place { .. }
+        | EngineCommand::Cancel { .. }
+        | EngineCommand::Amend { .. }
+        | EngineCommand::MassCancel { .. } => {}
     }
 }
 
-fn matching_orders(
+fn send_order_reply<T>(
+    reply: OrderReply,
+    order_store: &OrderStore,
+    id: Uuid,
+    result: Result<T, RejectReason>,
+) {
+    let response = result.and_then(|_| {
+        order_store
+            .get(&id)
+            .cloned()
+            .ok_or(RejectReason::UnknownOrder)
+    });
+    let _ = reply.send(response);
+}
+
+fn place_order(
     order_message: &OrderMessage,
     orderbook: &mut OrderBook,
+    order_store: &mut OrderStore,
     dealbook: &mut DealBook,
-) {
-    match order_message.side {
+) -> Result<Vec<ExecutionReport>, RejectReason> {
+    if order_message.quantity == 0 {
+        return Err(RejectReason::InvalidQuantity);
+    }
+    if order_store.contains(&order_message.id) {
+        return Err(RejectReason::DuplicateOrderId);
+    }
+
+    let order = OrderRecord::new(order_message);
+    order_store.accounts.reserve(&order)?;
+    matching_orders(&order, orderbook, dealbook);
+    order_store.insert(order);
+    Ok(order_store.apply_deals(&dealbook.deals))
+}
+
+fn cancel_order(
+    id: Uuid,
+    account: Option<&str>,
+    orderbook: &mut OrderBook,
+    order_store: &mut OrderStore,
+) -> Result<ExecutionReport, RejectReason> {
+    let report = order_store.cancel_order(&id, account)?;
+    orderbook.remove_orders(&HashSet::from([id]));
+    Ok(report)
+}
+
+///резерв пересчитывается до изменения книги: при нехватке средств ордер остаётся прежним;
+///первым идёт отчёт об изменении, за ним исполнения, если ордер сопоставился заново;
+///уменьшение остатка по той же цене сохраняет место в очереди,
+///остальные изменения проходят через снятие остатка с книги и повторное сопоставление
+fn amend_order(
+    id: Uuid,
+    account: Option<&str>,
+    price: Option<u32>,
+    quantity: Option<u32>,
+    orderbook: &mut OrderBook,
+    order_store: &mut OrderStore,
+    dealbook: &mut DealBook,
+) -> Result<Vec<ExecutionReport>, RejectReason> {
+    let mut order = order_store.open_order(&id, account)?.clone();
+    let (old_price, old_remaining_quantity) = (order.price, order.remaining_quantity);
+    order.amend(
+        price.unwrap_or(order.price),
+        quantity.unwrap_or(order.quantity),
+    )?;
+    order_store.accounts.reserve(&order)?;
+    order_store.insert(order.clone());
+    let mut execution_reports = vec![ExecutionReport::amend(&order)];
+
+    if order.price == old_price && order.remaining_quantity <= old_remaining_quantity {
+        orderbook.reduce_order(id, &order.side, order.remaining_quantity);
+        return Ok(execution_reports);
+    }
+
+    orderbook.remove_orders(&HashSet::from([id]));
+    matching_orders(&order, orderbook, dealbook);
+    execution_reports.extend(order_store.apply_deals(&dealbook.deals));
+    Ok(execution_reports)
+}
+
+fn mass_cancel(
+    symbol: &str,
+    filter: &CancelFilter,
+    orderbook: &mut OrderBook,
+    order_store: &mut OrderStore,
+) -> (MassCancelReport, Vec<ExecutionReport>) {
+    if !filter.matches_instrument(symbol) {
+        return (MassCancelReport::new(Vec::new()), Vec::new());
+    }
+
+    let execution_reports = order_store.cancel(filter);
+    let canceled: Vec<Uuid> = execution_reports
+        .iter()
+        .map(|report| report.order_id)
+        .collect();
+    orderbook.remove_orders(&canceled.iter().copied().collect::<HashSet<_>>());
+    (MassCancelReport::new(canceled), execution_reports)
+}
+
+fn matching_orders(order: &OrderRecord, orderbook: &mut OrderBook, dealbook: &mut DealBook) {
+    match order.side {
         OrderSide::Ask => {
             let ask_order = AskOrder::new(
-                order_message.id,
-                order_message.quantity,
-                order_message.quantity,
-                order_message.price,
+                order.id,
+                order.quantity,
+                order.remaining_quantity,
+                order.price,
             );
             let updated_ask_order = asks_match_bids(ask_order, orderbook, dealbook);
             orderbook.asks_push(updated_ask_order);
         }
         OrderSide::Bid => {
             let bid_order = BidOrder::new(
-                order_message.id,
-                order_message.quantity,
-                order_message.quantity,
-                order_message.price,
+                order.id,
+                order.quantity,
+                order.remaining_quantity,
+                order.price,
             );
             let updated_bid_order = bids_match_asks(bid_order, orderbook, dealbook);
             orderbook.bids_push(updated_bid_order);
@@ -99,3 +364,78 @@ fn bids_match_asks(
         bid_order.price,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::account_book::AccountBook;
+    use crate::matching::models::execution_report::ExecutionKind;
+    use crate::matching::models::fee_schedule::FeeSchedule;
+
+    fn place(
+        side: OrderSide,
+        quantity: u32,
+        price: u32,
+        orderbook: &mut OrderBook,
+        order_store: &mut OrderStore,
+    ) -> Uuid {
+        let message = OrderMessage {
+            id: Uuid::new_v4(),
+            account: Some(String::from("alice")),
+            side,
+            quantity,
+            price,
+        };
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        place_order(&message, orderbook, order_store, &mut dealbook).unwrap();
+        message.id
+    }
+
+    fn amend(
+        id: Uuid,
+        price: u32,
+        quantity: u32,
+        orderbook: &mut OrderBook,
+        order_store: &mut OrderStore,
+    ) -> Vec<ExecutionReport> {
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        amend_order(
+            id,
+            Some("alice"),
+            Some(price),
+            Some(quantity),
+            orderbook,
+            order_store,
+            &mut dealbook,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_amend_reports_before_fills() {
+        let mut orderbook = OrderBook::new();
+        let mut accounts = AccountBook::new("BTC", "USDT", FeeSchedule::default());
+        accounts.deposit("alice", "BTC", 100).unwrap();
+        accounts.deposit("alice", "USDT", 100_000).unwrap();
+        let mut order_store = OrderStore::new(accounts, 100);
+        let ask = place(OrderSide::Ask, 10, 500, &mut orderbook, &mut order_store);
+        let bid = place(OrderSide::Bid, 10, 400, &mut orderbook, &mut order_store);
+
+        let reduced = amend(ask, 500, 4, &mut orderbook, &mut order_store);
+        assert_eq!(reduced.len(), 1);
+        assert_eq!(reduced[0].kind, ExecutionKind::Amend);
+        assert_eq!(reduced[0].remaining_quantity, 4);
+
+        let crossed = amend(bid, 500, 10, &mut orderbook, &mut order_store);
+        let kinds: Vec<ExecutionKind> = crossed.iter().map(|report| report.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ExecutionKind::Amend,
+                ExecutionKind::Fill,
+                ExecutionKind::Fill
+            ]
+        );
+        assert_eq!(crossed[0].price, 500);
+    }
+}
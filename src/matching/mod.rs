@@ -1,3 +1,18 @@
+pub mod candles;
 pub mod engine;
-pub mod models;
+pub mod models {
+    pub use matching_be::matching::models::*;
+
+    //внутреннее состояние движка: в библиотеку не входит
+    pub mod account_book;
+    pub mod ask_order;
+    pub mod bid_order;
+    pub mod dealbook;
+    pub mod depth_book;
+    pub mod engine_command;
+    pub mod order_store;
+    pub mod orderbook;
+}
+pub mod replay;
 pub mod send;
+pub mod ticker;
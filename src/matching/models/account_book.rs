@@ -0,0 +1,401 @@
+use crate::matching::models::execution_report::{ExecutionKind, ExecutionReport};
+use crate::matching::models::fee_schedule::{FeeSchedule, Liquidity};
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::order_side::OrderSide;
+use crate::matching::models::order_status::OrderStatus;
+use crate::matching::models::reject_reason::RejectReason;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    pub available: u64,
+    pub reserved: u64,
+}
+
+pub type AccountBalances = BTreeMap<String, Balance>;
+
+struct Reservation {
+    account: String,
+    asset: String,
+    amount: u64,
+}
+
+///балансы счетов по активам инструмента.
+///
+///Открытый ордер держит резерв: ask - base на остаток,
+///bid - quote на остаток по цене ордера вместе с комиссией по большей из ставок, округлённой
+///вверх за каждую единицу: так резерва хватает при любом дроблении исполнения.
+///Исполнение списывает резерв и зачисляет встречный актив, комиссия берётся в quote;
+///остаток резерва возвращается, когда ордер исполнен или отменён. Ордер без счёта не принимается
+pub struct AccountBook {
+    base: String,
+    quote: String,
+    fees: FeeSchedule,
+    balances: HashMap<String, AccountBalances>,
+    reservations: HashMap<Uuid, Reservation>,
+}
+
+impl AccountBook {
+    #[must_use]
+    pub fn new(base: &str, quote: &str, fees: FeeSchedule) -> Self {
+        Self {
+            base: base.to_owned(),
+            quote: quote.to_owned(),
+            fees,
+            balances: HashMap::new(),
+            reservations: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn fee(&self, liquidity: Liquidity, price: u32, quantity: u32) -> u64 {
+        self.fees.fee(liquidity, price, quantity)
+    }
+
+    #[must_use]
+    pub fn balances(&self, account: &str) -> AccountBalances {
+        self.balances.get(account).cloned().unwrap_or_default()
+    }
+
+    ///# Errors
+    ///`UnknownAsset`, если актив не относится к инструменту
+    pub fn deposit(
+        &mut self,
+        account: &str,
+        asset: &str,
+        amount: u64,
+    ) -> Result<AccountBalances, RejectReason> {
+        if asset != self.base && asset != self.quote {
+            return Err(RejectReason::UnknownAsset);
+        }
+        let balance = self.balance_mut(account, asset);
+        balance.available = balance.available.saturating_add(amount);
+        Ok(self.balances(account))
+    }
+
+    ///доводит резерв ордера до нужного на его остаток: при выставлении и при изменении цены или количества
+    ///# Errors
+    ///`MissingAccount` для ордера без счёта, `InsufficientFunds`, если доступного баланса не хватает на резерв
+    pub fn reserve(&mut self, order: &OrderRecord) -> Result<(), RejectReason> {
+        let Some(account) = order.account.as_deref() else {
+            return Err(RejectReason::MissingAccount);
+        };
+        let (asset, required) = self.required(order);
+        let reserved = self
+            .reservations
+            .get(&order.id)
+            .map_or(0, |reservation| reservation.amount);
+
+        let available = self
+            .balances
+            .get(account)
+            .and_then(|balances| balances.get(&asset))
+            .map_or(0, |balance| balance.available);
+        if required > reserved && available < required - reserved {
+            return Err(RejectReason::InsufficientFunds);
+        }
+
+        let balance = self.balance_mut(account, &asset);
+        balance.available = balance.available + reserved - required;
+        balance.reserved = balance.reserved + required - reserved;
+        self.reservations.insert(
+            order.id,
+            Reservation {
+                account: account.to_owned(),
+                asset,
+                amount: required,
+            },
+        );
+        Ok(())
+    }
+
+    ///списывает исполненную часть с резерва и зачисляет встречный актив
+    pub fn settle(&mut self, report: &ExecutionReport) {
+        let (Some(account), Some(price), Some(quantity)) = (
+            report.account.as_deref(),
+            report.last_price,
+            report.last_quantity,
+        ) else {
+            return;
+        };
+        if report.kind != ExecutionKind::Fill {
+            return;
+        }
+        let fee = report.fee.unwrap_or(0);
+        let notional = u64::from(price) * u64::from(quantity);
+        let (spent, (received_asset, received)) = match report.side {
+            OrderSide::Ask => (
+                u64::from(quantity),
+                (self.quote.clone(), notional.saturating_sub(fee)),
+            ),
+            OrderSide::Bid => (notional + fee, (self.base.clone(), u64::from(quantity))),
+        };
+
+        self.spend(report.order_id, account, &report.side, spent);
+        let balance = self.balance_mut(account, &received_asset);
+        balance.available = balance.available.saturating_add(received);
+        if report.status != OrderStatus::Open {
+            self.release(report.order_id);
+        }
+    }
+
+    pub fn release(&mut self, order_id: Uuid) {
+        let Some(reservation) = self.reservations.remove(&order_id) else {
+            return;
+        };
+        let balance = self.balance_mut(&reservation.account, &reservation.asset);
+        balance.reserved -= reservation.amount;
+        balance.available += reservation.amount;
+    }
+
+    ///резерв рассчитан на худший случай, поэтому исполнение сверх него - нарушение инварианта:
+    ///в отладке это паника, в релизе списание ограничивается резервом, чтобы не остановить движок
+    fn spend(&mut self, order_id: Uuid, account: &str, side: &OrderSide, amount: u64) {
+        let reserved = self
+            .reservations
+            .get(&order_id)
+            .map_or(0, |reservation| reservation.amount);
+        debug_assert!(
+            amount <= reserved,
+            "order {order_id} spends {amount} over its reservation of {reserved}"
+        );
+        if amount > reserved {
+            eprintln!("Order {order_id} spends {amount} over its reservation of {reserved}");
+        }
+        let amount = amount.min(reserved);
+        if let Some(reservation) = self.reservations.get_mut(&order_id) {
+            reservation.amount -= amount;
+        }
+        let asset = match side {
+            OrderSide::Ask => self.base.clone(),
+            OrderSide::Bid => self.quote.clone(),
+        };
+        let balance = self.balance_mut(account, &asset);
+        balance.reserved = balance.reserved.saturating_sub(amount);
+    }
+
+    fn required(&self, order: &OrderRecord) -> (String, u64) {
+        match order.side {
+            OrderSide::Ask => (self.base.clone(), u64::from(order.remaining_quantity)),
+            OrderSide::Bid => {
+                let quantity = u64::from(order.remaining_quantity);
+                let unit_fee = self.fee(Liquidity::Maker, order.price, 1).max(self.fee(
+                    Liquidity::Taker,
+                    order.price,
+                    1,
+                ));
+                (
+                    self.quote.clone(),
+                    (u64::from(order.price) * quantity)
+                        .saturating_add(unit_fee.saturating_mul(quantity)),
+                )
+            }
+        }
+    }
+
+    fn balance_mut(&mut self, account: &str, asset: &str) -> &mut Balance {
+        self.balances
+            .entry(account.to_owned())
+            .or_default()
+            .entry(asset.to_owned())
+            .or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::dealbook::DealBook;
+    use crate::matching::models::order_message::OrderMessage;
+    use chrono::Utc;
+
+    fn accounts(fees: FeeSchedule) -> AccountBook {
+        let mut accounts = AccountBook::new("BTC", "USDT", fees);
+        accounts.deposit("alice", "BTC", 100).unwrap();
+        accounts.deposit("bob", "USDT", 10_000).unwrap();
+        accounts
+    }
+
+    fn order(account: &str, side: OrderSide, quantity: u32, price: u32) -> OrderRecord {
+        OrderRecord::new(&OrderMessage {
+            id: Uuid::new_v4(),
+            account: Some(account.to_owned()),
+            side,
+            quantity,
+            price,
+        })
+    }
+
+    fn balance(accounts: &AccountBook, account: &str, asset: &str) -> Balance {
+        accounts.balances(account)[asset]
+    }
+
+    fn fill(
+        accounts: &mut AccountBook,
+        order: &mut OrderRecord,
+        liquidity: Liquidity,
+        price: u32,
+        quantity: u32,
+    ) {
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        dealbook.push(order.side.clone(), price, quantity, order.id, order.id);
+        let deal = &dealbook.deals[0];
+        order.fill(deal.time, price, quantity);
+        let fee = accounts.fee(liquidity, price, quantity);
+        accounts.settle(&ExecutionReport::fill(order, deal, liquidity, fee));
+    }
+
+    #[test]
+    fn test_deposit_unknown_asset() {
+        let mut accounts = accounts(FeeSchedule::default());
+
+        assert_eq!(
+            accounts.deposit("alice", "ETH", 1),
+            Err(RejectReason::UnknownAsset)
+        );
+        assert_eq!(
+            balance(&accounts, "alice", "BTC"),
+            Balance {
+                available: 100,
+                reserved: 0,
+            }
+        );
+        assert!(accounts.balances("carol").is_empty());
+    }
+
+    #[test]
+    fn test_reserve_by_side() {
+        let mut accounts = accounts(FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 20,
+        });
+
+        accounts
+            .reserve(&order("alice", OrderSide::Ask, 40, 500))
+            .unwrap();
+        accounts
+            .reserve(&order("bob", OrderSide::Bid, 10, 500))
+            .unwrap();
+
+        assert_eq!(balance(&accounts, "alice", "BTC").reserved, 40);
+        assert_eq!(
+            balance(&accounts, "bob", "USDT"),
+            Balance {
+                available: 4_990,
+                reserved: 5_010,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reject_over_available_balance() {
+        let mut accounts = accounts(FeeSchedule::default());
+
+        assert_eq!(
+            accounts.reserve(&order("alice", OrderSide::Ask, 101, 500)),
+            Err(RejectReason::InsufficientFunds)
+        );
+        assert_eq!(
+            accounts.reserve(&order("bob", OrderSide::Bid, 21, 500)),
+            Err(RejectReason::InsufficientFunds)
+        );
+        assert_eq!(balance(&accounts, "alice", "BTC").reserved, 0);
+        assert_eq!(balance(&accounts, "bob", "USDT").reserved, 0);
+        assert_eq!(
+            accounts.reserve(&order("carol", OrderSide::Bid, 1, 500)),
+            Err(RejectReason::InsufficientFunds)
+        );
+        assert_eq!(
+            accounts.reserve(&OrderRecord::new(&OrderMessage {
+                id: Uuid::new_v4(),
+                account: None,
+                side: OrderSide::Bid,
+                quantity: 1,
+                price: 500,
+            })),
+            Err(RejectReason::MissingAccount)
+        );
+    }
+
+    #[test]
+    fn test_settle_and_release() {
+        let mut accounts = accounts(FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 20,
+        });
+        let mut ask = order("alice", OrderSide::Ask, 40, 500);
+        let mut bid = order("bob", OrderSide::Bid, 10, 510);
+        accounts.reserve(&ask).unwrap();
+        accounts.reserve(&bid).unwrap();
+
+        fill(&mut accounts, &mut ask, Liquidity::Maker, 500, 10);
+        fill(&mut accounts, &mut bid, Liquidity::Taker, 500, 10);
+
+        assert_eq!(
+            balance(&accounts, "alice", "BTC"),
+            Balance {
+                available: 60,
+                reserved: 30,
+            }
+        );
+        assert_eq!(balance(&accounts, "alice", "USDT").available, 5_000 - 5);
+        assert_eq!(
+            balance(&accounts, "bob", "USDT"),
+            Balance {
+                available: 10_000 - 5_000 - 10,
+                reserved: 0,
+            }
+        );
+        assert_eq!(balance(&accounts, "bob", "BTC").available, 10);
+
+        ask.cancel();
+        accounts.release(ask.id);
+        assert_eq!(
+            balance(&accounts, "alice", "BTC"),
+            Balance {
+                available: 90,
+                reserved: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reservation_covers_fee_rounding_per_fill() {
+        let mut accounts = accounts(FeeSchedule {
+            maker_bps: 1,
+            taker_bps: 1,
+        });
+        let mut bid = order("bob", OrderSide::Bid, 10, 500);
+        accounts.reserve(&bid).unwrap();
+        assert_eq!(balance(&accounts, "bob", "USDT").reserved, 5_010);
+
+        for _ in 0..10 {
+            fill(&mut accounts, &mut bid, Liquidity::Taker, 500, 1);
+        }
+
+        assert_eq!(
+            balance(&accounts, "bob", "USDT"),
+            Balance {
+                available: 10_000 - 5_010,
+                reserved: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_amend_adjusts_reservation() {
+        let mut accounts = accounts(FeeSchedule::default());
+        let mut bid = order("bob", OrderSide::Bid, 10, 500);
+        accounts.reserve(&bid).unwrap();
+
+        bid.amend(400, 10).unwrap();
+        accounts.reserve(&bid).unwrap();
+        assert_eq!(balance(&accounts, "bob", "USDT").reserved, 4_000);
+
+        bid.amend(600, 20).unwrap();
+        assert_eq!(accounts.reserve(&bid), Err(RejectReason::InsufficientFunds));
+        assert_eq!(balance(&accounts, "bob", "USDT").reserved, 4_000);
+    }
+}
@@ -7,35 +7,41 @@ pub struct AskOrder {
     pub quantity: u32,
     pub current_quantity: u32,
     pub price: u32,
+    pub order_ref: u64, //публичный номер в книге, присваивается при постановке и задаёт очередь внутри цены
 }
 
 impl Eq for AskOrder {}
 
 impl PartialEq for AskOrder {
     fn eq(&self, other: &Self) -> bool {
-        self.price == other.price
+        self.price == other.price && self.order_ref == other.order_ref
     }
 }
 
 impl PartialOrd for AskOrder {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(other.price.cmp(&self.price))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for AskOrder {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.price.cmp(&self.price)
+        other
+            .price
+            .cmp(&self.price)
+            .then_with(|| other.order_ref.cmp(&self.order_ref))
     }
 }
 
 impl AskOrder {
+    #[must_use]
     pub const fn new(id: Uuid, quantity: u32, current_quantity: u32, price: u32) -> Self {
         Self {
             id,
             quantity,
             current_quantity,
             price,
+            order_ref: 0,
         }
     }
 }
@@ -110,15 +116,15 @@ mod tests {
         let order1 = AskOrder::new(id1, 100, 100, 50);
         let order2 = AskOrder::new(id2, 200, 200, 50);
 
-        let mut orders = vec![order1.clone(), order2.clone()];
-        orders.sort();
-        assert_eq!(orders[0].id, order1.id);
-        assert_eq!(orders[1].id, order2.id);
+        let mut sorted = [order1.clone(), order2.clone()];
+        sorted.sort();
+        assert_eq!(sorted[0].id, order1.id);
+        assert_eq!(sorted[1].id, order2.id);
     }
 
     #[test]
     fn test_sorting() {
-        let mut orders = vec![
+        let mut orders = [
             AskOrder::new(Uuid::new_v4(), 100, 100, 70),
             AskOrder::new(Uuid::new_v4(), 100, 100, 50),
             AskOrder::new(Uuid::new_v4(), 100, 100, 60),
@@ -142,14 +148,25 @@ mod tests {
         let ask_low = AskOrder::new(Uuid::new_v4(), 100, 100, price_low);
         let ask_high = AskOrder::new(Uuid::new_v4(), 100, 100, price_high);
 
-        let mut bids = vec![bid_low.clone(), bid_high.clone()];
+        let mut bids = [bid_low, bid_high];
         bids.sort();
         assert_eq!(bids[0].price, price_low);
         assert_eq!(bids[1].price, price_high);
 
-        let mut asks = vec![ask_low.clone(), ask_high.clone()];
+        let mut asks = [ask_low, ask_high];
         asks.sort();
         assert_eq!(asks[0].price, price_high);
         assert_eq!(asks[1].price, price_low);
     }
+
+    #[test]
+    fn test_time_priority_within_price() {
+        let mut earlier = AskOrder::new(Uuid::new_v4(), 100, 100, 50);
+        let mut later = AskOrder::new(Uuid::new_v4(), 100, 100, 50);
+        earlier.order_ref = 1;
+        later.order_ref = 2;
+
+        assert_eq!(earlier.cmp(&later), Ordering::Greater);
+        assert_ne!(earlier, later);
+    }
 }
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+///лучшие цены с объёмами на них и последняя сделка
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BestBidOffer {
+    pub bid_price: Option<u32>,
+    pub bid_quantity: Option<u32>,
+    pub ask_price: Option<u32>,
+    pub ask_quantity: Option<u32>,
+    pub last_price: Option<u32>,
+    pub last_quantity: Option<u32>,
+}
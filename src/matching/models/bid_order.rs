@@ -7,35 +7,40 @@ pub struct BidOrder {
     pub quantity: u32,
     pub current_quantity: u32,
     pub price: u32,
+    pub order_ref: u64, //публичный номер в книге, присваивается при постановке и задаёт очередь внутри цены
 }
 
 impl Eq for BidOrder {}
 
 impl PartialEq for BidOrder {
     fn eq(&self, other: &Self) -> bool {
-        self.price == other.price
+        self.price == other.price && self.order_ref == other.order_ref
     }
 }
 
 impl PartialOrd for BidOrder {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.price.cmp(&other.price))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for BidOrder {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.price.cmp(&other.price)
+        self.price
+            .cmp(&other.price)
+            .then_with(|| other.order_ref.cmp(&self.order_ref))
     }
 }
 
 impl BidOrder {
+    #[must_use]
     pub const fn new(id: Uuid, quantity: u32, current_quantity: u32, price: u32) -> Self {
         Self {
             id,
             quantity,
             current_quantity,
             price,
+            order_ref: 0,
         }
     }
 }
@@ -110,16 +115,16 @@ mod tests {
         let order1 = BidOrder::new(id1, 100, 100, 50);
         let order2 = BidOrder::new(id2, 200, 200, 50);
 
-        let mut orders = vec![order1.clone(), order2.clone()];
-        orders.sort();
+        let mut sorted = [order1.clone(), order2.clone()];
+        sorted.sort();
 
-        assert_eq!(orders[0].id, order1.id);
-        assert_eq!(orders[1].id, order2.id);
+        assert_eq!(sorted[0].id, order1.id);
+        assert_eq!(sorted[1].id, order2.id);
     }
 
     #[test]
     fn test_sorting() {
-        let mut orders = vec![
+        let mut orders = [
             BidOrder::new(Uuid::new_v4(), 100, 100, 70),
             BidOrder::new(Uuid::new_v4(), 100, 100, 50),
             BidOrder::new(Uuid::new_v4(), 100, 100, 60),
@@ -144,15 +149,26 @@ mod tests {
         let ask_high = AskOrder::new(Uuid::new_v4(), 100, 100, price_high);
 
         // For bids: Lower price has higher priority (sorts first)
-        let mut bids = vec![bid_low.clone(), bid_high.clone()];
+        let mut bids = [bid_low, bid_high];
         bids.sort();
         assert_eq!(bids[0].price, price_low);
         assert_eq!(bids[1].price, price_high);
 
         // For asks: Higher price has higher priority (sorts first)
-        let mut asks = vec![ask_low.clone(), ask_high.clone()];
+        let mut asks = [ask_low, ask_high];
         asks.sort();
         assert_eq!(asks[0].price, price_high);
         assert_eq!(asks[1].price, price_low);
     }
+
+    #[test]
+    fn test_time_priority_within_price() {
+        let mut earlier = BidOrder::new(Uuid::new_v4(), 100, 100, 50);
+        let mut later = BidOrder::new(Uuid::new_v4(), 100, 100, 50);
+        earlier.order_ref = 1;
+        later.order_ref = 2;
+
+        assert_eq!(earlier.cmp(&later), Ordering::Greater);
+        assert_ne!(earlier, later);
+    }
 }
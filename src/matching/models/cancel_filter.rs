@@ -0,0 +1,99 @@
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::order_side::OrderSide;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct CancelFilter {
+    pub account: Option<String>,
+    pub side: Option<OrderSide>,
+    pub instrument: Option<String>,
+    pub min_price: Option<u32>,
+    pub max_price: Option<u32>,
+}
+
+impl CancelFilter {
+    #[must_use]
+    pub fn matches(&self, order: &OrderRecord) -> bool {
+        self.account
+            .as_deref()
+            .is_none_or(|account| order.account.as_deref() == Some(account))
+            && self.side.as_ref().is_none_or(|side| order.side == *side)
+            && self
+                .min_price
+                .is_none_or(|min_price| order.price >= min_price)
+            && self
+                .max_price
+                .is_none_or(|max_price| order.price <= max_price)
+    }
+
+    #[must_use]
+    pub fn matches_instrument(&self, symbol: &str) -> bool {
+        self.instrument
+            .as_deref()
+            .is_none_or(|instrument| instrument == symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_message::OrderMessage;
+    use uuid::Uuid;
+
+    fn order(account: &str, side: OrderSide, price: u32) -> OrderRecord {
+        OrderRecord::new(&OrderMessage {
+            id: Uuid::new_v4(),
+            account: Some(account.to_owned()),
+            side,
+            quantity: 10,
+            price,
+        })
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = CancelFilter::default();
+
+        assert!(filter.matches(&order("alice", OrderSide::Ask, 500)));
+        assert!(filter.matches(&order("bob", OrderSide::Bid, 0)));
+        assert!(filter.matches_instrument("BTCUSDT"));
+    }
+
+    #[test]
+    fn test_filter_by_account_and_side() {
+        let filter = CancelFilter {
+            account: Some(String::from("alice")),
+            side: Some(OrderSide::Bid),
+            ..CancelFilter::default()
+        };
+
+        assert!(filter.matches(&order("alice", OrderSide::Bid, 500)));
+        assert!(!filter.matches(&order("alice", OrderSide::Ask, 500)));
+        assert!(!filter.matches(&order("bob", OrderSide::Bid, 500)));
+    }
+
+    #[test]
+    fn test_filter_by_price_range_inclusive() {
+        let filter = CancelFilter {
+            min_price: Some(100),
+            max_price: Some(200),
+            ..CancelFilter::default()
+        };
+
+        assert!(!filter.matches(&order("alice", OrderSide::Ask, 99)));
+        assert!(filter.matches(&order("alice", OrderSide::Ask, 100)));
+        assert!(filter.matches(&order("alice", OrderSide::Ask, 200)));
+        assert!(!filter.matches(&order("alice", OrderSide::Ask, 201)));
+    }
+
+    #[test]
+    fn test_filter_by_instrument() {
+        let filter = CancelFilter {
+            instrument: Some(String::from("BTCUSDT")),
+            ..CancelFilter::default()
+        };
+
+        assert!(filter.matches_instrument("BTCUSDT"));
+        assert!(!filter.matches_instrument("ETHUSDT"));
+    }
+}
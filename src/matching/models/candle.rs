@@ -0,0 +1,520 @@
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::trade_feed::PublicTrade;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+const DEFAULT_CANDLES: usize = 500;
+const MAX_CANDLES: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "1s")]
+    OneSecond,
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl CandleInterval {
+    pub const ALL: [Self; 5] = [
+        Self::OneSecond,
+        Self::OneMinute,
+        Self::FiveMinutes,
+        Self::OneHour,
+        Self::OneDay,
+    ];
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::OneSecond => "1s",
+            Self::OneMinute => "1m",
+            Self::FiveMinutes => "5m",
+            Self::OneHour => "1h",
+            Self::OneDay => "1d",
+        }
+    }
+
+    #[must_use]
+    pub const fn duration(self) -> TimeDelta {
+        match self {
+            Self::OneSecond => TimeDelta::seconds(1),
+            Self::OneMinute => TimeDelta::minutes(1),
+            Self::FiveMinutes => TimeDelta::minutes(5),
+            Self::OneHour => TimeDelta::hours(1),
+            Self::OneDay => TimeDelta::days(1),
+        }
+    }
+
+    ///начало бара, в который попадает время; бары выровнены по UTC
+    #[must_use]
+    pub fn open_time(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let millis = time.timestamp_millis();
+        let step = self.duration().num_milliseconds();
+        DateTime::from_timestamp_millis(millis - millis.rem_euclid(step)).unwrap_or(time)
+    }
+}
+
+///бар OHLCV; closed - бар закрыт и больше не изменится, иначе это формирующийся бар
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    pub interval: CandleInterval,
+    pub open_time: DateTime<Utc>,
+    pub close_time: DateTime<Utc>,
+    pub open: u32,
+    pub high: u32,
+    pub low: u32,
+    pub close: u32,
+    pub volume: u64,
+    pub trades: u64,
+    pub closed: bool,
+}
+
+impl Candle {
+    fn open(interval: CandleInterval, open_time: DateTime<Utc>, trade: &PublicTrade) -> Self {
+        Self {
+            interval,
+            open_time,
+            close_time: open_time + interval.duration(),
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: u64::from(trade.quantity),
+            trades: 1,
+            closed: false,
+        }
+    }
+
+    fn add(&mut self, trade: &PublicTrade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += u64::from(trade.quantity);
+        self.trades += 1;
+    }
+}
+
+///from и to ограничивают время открытия бара: from включительно, to исключая;
+///из подходящих баров возвращаются последние limit
+#[derive(Clone, Debug, Deserialize)]
+pub struct CandlesQuery {
+    pub symbol: Option<String>,
+    pub interval: CandleInterval,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl CandlesQuery {
+    #[must_use]
+    pub const fn latest(interval: CandleInterval, limit: usize) -> Self {
+        Self {
+            symbol: None,
+            interval,
+            from: None,
+            to: None,
+            limit: Some(limit),
+        }
+    }
+
+    fn contains(&self, candle: &Candle) -> bool {
+        self.from.is_none_or(|from| candle.open_time >= from)
+            && self.to.is_none_or(|to| candle.open_time < to)
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+            .map_or(DEFAULT_CANDLES, |limit| limit.min(MAX_CANDLES))
+    }
+}
+
+struct CandleSeries {
+    interval: CandleInterval,
+    closed: VecDeque<Candle>,
+    forming: Option<Candle>,
+}
+
+impl CandleSeries {
+    ///бар, закрытый по таймеру, уже разослан: опоздавшая к нему сделка попадает в следующий бар
+    fn add(&mut self, trade: &PublicTrade, events: &mut Vec<Candle>) {
+        if let Some(forming) = self.forming.as_mut()
+            && trade.time < forming.close_time
+        {
+            forming.add(trade);
+            return;
+        }
+        self.close(events);
+        let open_time = self.interval.open_time(trade.time);
+        let open_time = self
+            .closed
+            .back()
+            .map_or(open_time, |last| open_time.max(last.close_time));
+        self.forming = Some(Candle::open(self.interval, open_time, trade));
+    }
+
+    ///сделка, пропущенная при отставании ленты, попадает в свой бар, даже если он уже закрыт;
+    ///возвращает время открытия закрытого бара, который изменился
+    fn restore(&mut self, trade: &PublicTrade, events: &mut Vec<Candle>) -> Option<DateTime<Utc>> {
+        let tail = self
+            .forming
+            .as_ref()
+            .map(|forming| forming.open_time)
+            .or_else(|| self.closed.back().map(|last| last.close_time));
+        if tail.is_none_or(|tail| trade.time >= tail) {
+            self.add(trade, events);
+            return None;
+        }
+        let index = self
+            .closed
+            .partition_point(|candle| candle.close_time <= trade.time);
+        if let Some(candle) = self.closed.get_mut(index)
+            && candle.open_time <= trade.time
+        {
+            candle.add(trade);
+            return Some(candle.open_time);
+        }
+        //в разрыве между барами: новый закрытый бар не заходит на соседей
+        let open_time = self.interval.open_time(trade.time);
+        let open_time = index
+            .checked_sub(1)
+            .and_then(|previous| self.closed.get(previous))
+            .map_or(open_time, |previous| open_time.max(previous.close_time));
+        let mut candle = Candle::open(self.interval, open_time, trade);
+        if let Some(next) = self.closed.get(index).or(self.forming.as_ref()) {
+            candle.close_time = candle.close_time.min(next.open_time);
+        }
+        candle.closed = true;
+        self.closed.insert(index, candle);
+        Some(open_time)
+    }
+
+    fn close(&mut self, events: &mut Vec<Candle>) {
+        if let Some(mut candle) = self.forming.take() {
+            candle.closed = true;
+            events.push(candle.clone());
+            self.closed.push_back(candle);
+        }
+    }
+
+    fn candles(&self, query: &CandlesQuery) -> Vec<Candle> {
+        let mut candles: Vec<Candle> = self
+            .closed
+            .iter()
+            .chain(self.forming.as_ref())
+            .rev()
+            .filter(|candle| query.contains(candle))
+            .take(query.limit())
+            .cloned()
+            .collect();
+        candles.reverse();
+        candles
+    }
+}
+
+///бары всех интервалов по ленте сделок; каждое изменение бара получает общий для всех интервалов seq.
+///Бар без сделок не создаётся, поэтому между барами возможны разрывы
+pub struct CandleBook {
+    capacity: usize,
+    seq: u64,
+    last_trade_id: u64,
+    series: Vec<CandleSeries>,
+}
+
+impl CandleBook {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seq: 0,
+            last_trade_id: 0,
+            series: CandleInterval::ALL
+                .into_iter()
+                .map(|interval| CandleSeries {
+                    interval,
+                    closed: VecDeque::new(),
+                    forming: None,
+                })
+                .collect(),
+        }
+    }
+
+    ///id последней учтённой сделки: после отставания ленты с него начинается восстановление
+    #[must_use]
+    pub const fn last_trade_id(&self) -> u64 {
+        self.last_trade_id
+    }
+
+    ///закрытые сделками бары, затем по одному обновлению формирующегося бара каждого интервала;
+    ///уже учтённые сделки пропускаются
+    pub fn apply(&mut self, trades: &[PublicTrade]) -> Vec<FeedMessage<Candle>> {
+        let trades = self.new_trades(trades);
+        if trades.is_empty() {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        for series in &mut self.series {
+            for trade in &trades {
+                series.add(trade, &mut events);
+            }
+        }
+        events.extend(
+            self.series
+                .iter()
+                .filter_map(|series| series.forming.clone()),
+        );
+        self.publish(events)
+    }
+
+    ///сделки, пропущенные при отставании ленты: закрытые бары, в которые они попали, рассылаются заново
+    pub fn restore(&mut self, trades: &[PublicTrade]) -> Vec<FeedMessage<Candle>> {
+        let trades = self.new_trades(trades);
+        if trades.is_empty() {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        for series in &mut self.series {
+            let mut restored: Vec<DateTime<Utc>> = Vec::new();
+            for trade in &trades {
+                restored.extend(series.restore(trade, &mut events));
+            }
+            events.extend(
+                series
+                    .closed
+                    .iter()
+                    .filter(|candle| restored.contains(&candle.open_time))
+                    .cloned(),
+            );
+        }
+        events.extend(
+            self.series
+                .iter()
+                .filter_map(|series| series.forming.clone()),
+        );
+        self.publish(events)
+    }
+
+    fn new_trades<'a>(&mut self, trades: &'a [PublicTrade]) -> Vec<&'a PublicTrade> {
+        let last_trade_id = self.last_trade_id;
+        let trades: Vec<&PublicTrade> = trades
+            .iter()
+            .filter(|trade| trade.id > last_trade_id)
+            .collect();
+        if let Some(last) = trades.last() {
+            self.last_trade_id = last.id;
+        }
+        trades
+    }
+
+    ///закрывает бары, время которых вышло к now, даже если новых сделок не было
+    pub fn close_expired(&mut self, now: DateTime<Utc>) -> Vec<FeedMessage<Candle>> {
+        let mut events = Vec::new();
+        for series in &mut self.series {
+            if series
+                .forming
+                .as_ref()
+                .is_some_and(|forming| forming.close_time <= now)
+            {
+                series.close(&mut events);
+            }
+        }
+        self.publish(events)
+    }
+
+    #[must_use]
+    pub fn candles(&self, query: &CandlesQuery) -> FeedMessage<Vec<Candle>> {
+        let candles = self
+            .series
+            .iter()
+            .find(|series| series.interval == query.interval)
+            .map(|series| series.candles(query))
+            .unwrap_or_default();
+        FeedMessage::snapshot(self.seq, candles)
+    }
+
+    fn publish(&mut self, events: Vec<Candle>) -> Vec<FeedMessage<Candle>> {
+        for series in &mut self.series {
+            while series.closed.len() > self.capacity {
+                series.closed.pop_front();
+            }
+        }
+        events
+            .into_iter()
+            .map(|candle| {
+                self.seq += 1;
+                FeedMessage::update(self.seq, candle)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_side::OrderSide;
+
+    fn time(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(millis).unwrap()
+    }
+
+    ///id растут вместе со временем сделки, как у сделок движка
+    fn trade(millis: i64, price: u32, quantity: u32) -> PublicTrade {
+        PublicTrade {
+            id: millis.unsigned_abs() + 1,
+            price,
+            quantity,
+            side: OrderSide::Bid,
+            time: time(millis),
+        }
+    }
+
+    fn of(events: &[FeedMessage<Candle>], interval: CandleInterval) -> Vec<&Candle> {
+        events
+            .iter()
+            .map(|event| &event.data)
+            .filter(|candle| candle.interval == interval)
+            .collect()
+    }
+
+    #[test]
+    fn test_open_time_is_aligned() {
+        let trade_time = time(90_061_500);
+
+        assert_eq!(
+            CandleInterval::OneSecond.open_time(trade_time),
+            time(90_061_000)
+        );
+        assert_eq!(
+            CandleInterval::OneMinute.open_time(trade_time),
+            time(90_060_000)
+        );
+        assert_eq!(
+            CandleInterval::FiveMinutes.open_time(trade_time),
+            time(90_000_000)
+        );
+        assert_eq!(
+            CandleInterval::OneHour.open_time(trade_time),
+            time(90_000_000)
+        );
+        assert_eq!(
+            CandleInterval::OneDay.open_time(trade_time),
+            time(86_400_000)
+        );
+    }
+
+    #[test]
+    fn test_forming_bar_ohlcv() {
+        let mut book = CandleBook::new(10);
+
+        let events = book.apply(&[trade(100, 10, 1), trade(200, 12, 2), trade(300, 9, 3)]);
+        let events = book
+            .apply(&[trade(400, 11, 4)])
+            .into_iter()
+            .chain(events)
+            .collect::<Vec<_>>();
+
+        let forming = of(&events, CandleInterval::OneSecond)[0];
+        assert_eq!(
+            (forming.open, forming.high, forming.low, forming.close),
+            (10, 12, 9, 11)
+        );
+        assert_eq!(forming.volume, 10);
+        assert_eq!(forming.trades, 4);
+        assert!(!forming.closed);
+        assert_eq!(events.len(), 10);
+        assert_eq!(events[4].seq, 10);
+    }
+
+    #[test]
+    fn test_trade_closes_previous_bar() {
+        let mut book = CandleBook::new(10);
+        book.apply(&[trade(100, 10, 1)]);
+
+        let events = book.apply(&[trade(1_500, 20, 1)]);
+
+        let second = of(&events, CandleInterval::OneSecond);
+        assert_eq!(second.len(), 2);
+        assert!(second[0].closed);
+        assert_eq!(second[0].close, 10);
+        assert_eq!(second[1].open_time, time(1_000));
+        assert!(!second[1].closed);
+        assert_eq!(of(&events, CandleInterval::OneMinute).len(), 1);
+    }
+
+    #[test]
+    fn test_close_expired_without_trades() {
+        let mut book = CandleBook::new(10);
+        book.apply(&[trade(100, 10, 1)]);
+
+        assert!(book.close_expired(time(999)).is_empty());
+        let events = book.close_expired(time(1_000));
+        assert_eq!(events.len(), 1);
+        assert!(events[0].data.closed);
+        assert_eq!(events[0].seq, 6);
+
+        let late = book.apply(&[trade(900, 11, 1)]);
+        assert_eq!(
+            of(&late, CandleInterval::OneSecond)[0].open_time,
+            time(1_000)
+        );
+    }
+
+    #[test]
+    fn test_candles_query() {
+        let mut book = CandleBook::new(2);
+        for second in 0..4 {
+            book.apply(&[trade(second * 1_000, 10, 1)]);
+        }
+
+        let snapshot = book.candles(&CandlesQuery::latest(CandleInterval::OneSecond, 10));
+        let open_times: Vec<_> = snapshot
+            .data
+            .iter()
+            .map(|candle| candle.open_time)
+            .collect();
+        assert_eq!(open_times, vec![time(1_000), time(2_000), time(3_000)]);
+        assert_eq!(snapshot.seq, 23);
+
+        let query = CandlesQuery {
+            from: Some(time(2_000)),
+            to: Some(time(3_000)),
+            ..CandlesQuery::latest(CandleInterval::OneSecond, 10)
+        };
+        assert_eq!(book.candles(&query).data.len(), 1);
+        assert_eq!(
+            book.candles(&CandlesQuery::latest(CandleInterval::OneSecond, 1))
+                .data[0]
+                .open_time,
+            time(3_000)
+        );
+    }
+
+    #[test]
+    fn test_restore_updates_closed_bar() {
+        let mut book = CandleBook::new(10);
+        book.apply(&[trade(100, 10, 1)]);
+        book.close_expired(time(1_000));
+
+        let missed = [trade(500, 12, 2), trade(1_500, 11, 1)];
+        let events = book.restore(&missed);
+
+        let second = of(&events, CandleInterval::OneSecond);
+        assert_eq!(second.len(), 2);
+        assert_eq!(second[0].open_time, time(0));
+        assert!(second[0].closed);
+        assert_eq!(
+            (second[0].high, second[0].volume, second[0].trades),
+            (12, 3, 2)
+        );
+        assert_eq!(second[1].open_time, time(1_000));
+        assert!(!second[1].closed);
+        assert_eq!(book.last_trade_id(), 1_501);
+        assert!(book.apply(&missed).is_empty());
+    }
+}
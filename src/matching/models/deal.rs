@@ -1,9 +1,13 @@
+use crate::matching::models::order_side::OrderSide;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Deal {
+    pub id: u64,
+    pub match_id: u64,   //общий у всех сделок одного входящего ордера
+    pub side: OrderSide, //сторона входящего ордера, который исполнился об книгу
     pub time: DateTime<Utc>,
     pub price: u32,
     pub quantity: u32,
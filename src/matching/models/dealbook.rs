@@ -1,32 +1,55 @@
 use crate::matching::models::deal::Deal;
-use chrono::Utc;
+use crate::matching::models::order_side::OrderSide;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+///сделки одного входящего ордера
 #[derive(Clone, Debug)]
 pub struct DealBook {
     pub deals: Vec<Deal>,
+    next_id: u64,
+    match_id: u64,
+    time: DateTime<Utc>,
 }
 
 impl DealBook {
-    pub const fn new() -> Self {
-        Self { deals: Vec::new() }
+    ///id сделок сквозные: книга следующей команды продолжает нумерацию с `next_id` предыдущей;
+    ///`match_id` и время движка общие для всех исполнений входящего ордера
+    #[must_use]
+    pub const fn new(next_id: u64, match_id: u64, time: DateTime<Utc>) -> Self {
+        Self {
+            deals: Vec::new(),
+            next_id,
+            match_id,
+            time,
+        }
+    }
+
+    #[must_use]
+    pub const fn next_id(&self) -> u64 {
+        self.next_id
     }
 
     pub fn push(
         &mut self,
+        side: OrderSide,
         bid_order_price: u32,
         deal_quantity: u32,
         ask_order_id: Uuid,
         bid_order_id: Uuid,
     ) {
         let new_deal: Deal = Deal {
-            time: Utc::now(),
+            id: self.next_id,
+            match_id: self.match_id,
+            side,
+            time: self.time,
             price: bid_order_price,
             quantity: deal_quantity,
             ask_order: ask_order_id,
             bid_order: bid_order_id,
         };
 
+        self.next_id += 1;
         self.deals.push(new_deal);
     }
 }
@@ -39,7 +62,7 @@ mod tests {
 
     #[test]
     fn test_empty_deal_book() {
-        let deal_book = DealBook { deals: Vec::new() };
+        let deal_book = DealBook::new(1, 1, Utc::now());
 
         assert_eq!(deal_book.deals.len(), 0);
         assert!(deal_book.deals.is_empty());
@@ -47,19 +70,18 @@ mod tests {
 
     #[test]
     fn test_edge_cases() {
-        let mut deal_book = DealBook { deals: Vec::new() };
+        let mut deal_book = DealBook::new(1, 1, Utc::now());
         let uuid = Uuid::new_v4();
 
-        deal_book.push(0, 0, uuid, uuid);
+        deal_book.push(OrderSide::Bid, 0, 0, uuid, uuid);
         assert_eq!(deal_book.deals[0].quantity, 0);
 
-        deal_book.push(u32::MAX, u32::MAX, uuid, uuid);
+        deal_book.push(OrderSide::Bid, u32::MAX, u32::MAX, uuid, uuid);
         assert_eq!(deal_book.deals[1].price, u32::MAX);
     }
 
     #[test]
     fn test_push_adds_valid_deal() {
-        let mut deal_book = DealBook { deals: Vec::new() };
         let ask_uuid = Uuid::new_v4();
         let bid_uuid = Uuid::new_v4();
 
@@ -67,7 +89,8 @@ mod tests {
         let quantity = 30;
 
         let time_before = Utc::now();
-        deal_book.push(price, quantity, ask_uuid, bid_uuid);
+        let mut deal_book = DealBook::new(1, 1, Utc::now());
+        deal_book.push(OrderSide::Bid, price, quantity, ask_uuid, bid_uuid);
         let time_after = Utc::now();
 
         assert_eq!(deal_book.deals.len(), 1);
@@ -86,14 +109,14 @@ mod tests {
 
     #[test]
     fn test_multiple_pushes() {
-        let mut deal_book = DealBook { deals: Vec::new() };
+        let mut deal_book = DealBook::new(1, 1, Utc::now());
         let uuid1 = Uuid::new_v4();
         let uuid2 = Uuid::new_v4();
 
-        deal_book.push(100, 10, uuid1, uuid2);
+        deal_book.push(OrderSide::Bid, 100, 10, uuid1, uuid2);
         assert_eq!(deal_book.deals.len(), 1);
 
-        deal_book.push(200, 20, uuid2, uuid1);
+        deal_book.push(OrderSide::Bid, 200, 20, uuid2, uuid1);
         assert_eq!(deal_book.deals.len(), 2);
 
         let last_deal = deal_book.deals.last().unwrap();
@@ -102,4 +125,32 @@ mod tests {
         assert_eq!(last_deal.ask_order, uuid2);
         assert_eq!(last_deal.bid_order, uuid1);
     }
+
+    #[test]
+    fn test_trade_ids_continue_across_books() {
+        let uuid = Uuid::new_v4();
+        let time = Utc::now();
+        let mut first = DealBook::new(1, 1, time);
+        first.push(OrderSide::Ask, 100, 1, uuid, uuid);
+        first.push(OrderSide::Ask, 100, 1, uuid, uuid);
+
+        let mut second = DealBook::new(first.next_id(), 2, Utc::now());
+        second.push(OrderSide::Bid, 100, 1, uuid, uuid);
+
+        let ids: Vec<u64> = first
+            .deals
+            .iter()
+            .chain(&second.deals)
+            .map(|deal| deal.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(second.deals[0].side, OrderSide::Bid);
+        assert!(
+            first
+                .deals
+                .iter()
+                .all(|deal| deal.match_id == 1 && deal.time == time)
+        );
+        assert_eq!(second.deals[0].match_id, 2);
+    }
 }
@@ -0,0 +1,158 @@
+use crate::matching::models::depth_of_market::{
+    DepthChange, DepthEntry, DepthOfMarket, DepthQuery, depth_checksum,
+};
+use crate::matching::models::order_side::OrderSide;
+use std::collections::BTreeMap;
+
+///стакан по уровням, собранный из снимка и инкрементальных изменений
+pub struct DepthBook {
+    asks: BTreeMap<u32, DepthEntry>,
+    bids: BTreeMap<u32, DepthEntry>,
+}
+
+impl DepthBook {
+    #[must_use]
+    pub fn new(dom: &DepthOfMarket) -> Self {
+        Self {
+            asks: dom
+                .ask
+                .iter()
+                .map(|entry| (entry.price, entry.clone()))
+                .collect(),
+            bids: dom
+                .bid
+                .iter()
+                .map(|entry| (entry.price, entry.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn apply(&mut self, changes: &[DepthChange]) {
+        for change in changes {
+            let levels = match change.side {
+                OrderSide::Ask => &mut self.asks,
+                OrderSide::Bid => &mut self.bids,
+            };
+            if change.quantity == 0 {
+                levels.remove(&change.price);
+            } else {
+                levels.insert(
+                    change.price,
+                    DepthEntry {
+                        price: change.price,
+                        quantity: change.quantity,
+                        orders: change.orders,
+                    },
+                );
+            }
+        }
+    }
+
+    ///сверяется с checksum сообщений depth, чтобы убедиться, что книга собрана верно
+    #[must_use]
+    pub fn checksum(&self) -> u32 {
+        depth_checksum(
+            self.asks
+                .values()
+                .map(|entry| (entry.price, entry.quantity)),
+            self.bids
+                .values()
+                .rev()
+                .map(|entry| (entry.price, entry.quantity)),
+        )
+    }
+
+    #[must_use]
+    pub fn view(&self, query: &DepthQuery) -> DepthOfMarket {
+        DepthOfMarket {
+            ask: query.levels(&OrderSide::Ask, self.asks.values().cloned()),
+            bid: query.levels(&OrderSide::Bid, self.bids.values().rev().cloned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(side: OrderSide, price: u32, quantity: u32) -> DepthChange {
+        DepthChange {
+            side,
+            price,
+            quantity,
+            orders: u32::from(quantity > 0),
+        }
+    }
+
+    #[test]
+    fn test_apply_changes_to_snapshot() {
+        let snapshot = DepthOfMarket {
+            ask: vec![DepthEntry {
+                price: 101,
+                quantity: 5,
+                orders: 1,
+            }],
+            bid: vec![DepthEntry {
+                price: 99,
+                quantity: 5,
+                orders: 1,
+            }],
+        };
+        let mut book = DepthBook::new(&snapshot);
+
+        book.apply(&[
+            change(OrderSide::Ask, 101, 0),
+            change(OrderSide::Ask, 103, 7),
+            change(OrderSide::Bid, 100, 2),
+        ]);
+
+        let view = book.view(&DepthQuery::default());
+        assert_eq!(
+            view.ask,
+            vec![DepthEntry {
+                price: 103,
+                quantity: 7,
+                orders: 1,
+            }]
+        );
+        assert_eq!(
+            view.bid,
+            vec![
+                DepthEntry {
+                    price: 100,
+                    quantity: 2,
+                    orders: 1,
+                },
+                DepthEntry {
+                    price: 99,
+                    quantity: 5,
+                    orders: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_view_with_query() {
+        let mut book = DepthBook::new(&DepthOfMarket::default());
+        book.apply(&[
+            change(OrderSide::Bid, 99, 1),
+            change(OrderSide::Bid, 98, 2),
+            change(OrderSide::Bid, 91, 3),
+        ]);
+
+        let query = DepthQuery {
+            depth: Some(1),
+            group: Some(10),
+        };
+
+        assert_eq!(
+            book.view(&query).bid,
+            vec![DepthEntry {
+                price: 90,
+                quantity: 6,
+                orders: 3,
+            }]
+        );
+    }
+}
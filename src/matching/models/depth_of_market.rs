@@ -1,13 +1,252 @@
-use serde::Serialize;
+use crate::matching::models::order_side::OrderSide;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DepthOfMarket {
     pub ask: Vec<DepthEntry>,
     pub bid: Vec<DepthEntry>,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DepthEntry {
     pub price: u32,
     pub quantity: u32,
+    pub orders: u32,
+}
+
+///изменение уровня стакана; quantity = 0 означает, что уровень удалён
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthChange {
+    pub side: OrderSide,
+    pub price: u32,
+    pub quantity: u32,
+    pub orders: u32,
+}
+
+///depth - количество уровней с каждой стороны, group - размер корзины в тиках
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct DepthQuery {
+    pub depth: Option<usize>,
+    pub group: Option<u32>,
+}
+
+impl DepthQuery {
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.depth.is_none() && self.group.is_none()
+    }
+
+    ///аски округляются вверх, биды вниз, чтобы корзины сторон не пересекались
+    const fn bucket(&self, side: &OrderSide, price: u32) -> u32 {
+        match self.group {
+            Some(group) if group > 1 => match side {
+                OrderSide::Ask => price.div_ceil(group).saturating_mul(group),
+                OrderSide::Bid => price / group * group,
+            },
+            _ => price,
+        }
+    }
+
+    ///уровни должны идти от лучшей цены к худшей
+    pub fn levels(
+        &self,
+        side: &OrderSide,
+        levels: impl Iterator<Item = DepthEntry>,
+    ) -> Vec<DepthEntry> {
+        let depth = self.depth.unwrap_or(usize::MAX);
+        let mut entries: Vec<DepthEntry> = Vec::new();
+        for level in levels {
+            let price = self.bucket(side, level.price);
+            let full = entries.len() == depth;
+            match entries.last_mut() {
+                Some(last) if last.price == price => {
+                    last.quantity += level.quantity;
+                    last.orders += level.orders;
+                }
+                _ if full => break,
+                _ => entries.push(DepthEntry { price, ..level }),
+            }
+        }
+        entries
+    }
+}
+
+///количество уровней с каждой стороны, входящих в контрольную сумму
+pub const CHECKSUM_DEPTH: usize = 10;
+
+///CRC32 верхних уровней стакана.
+///
+///Для i от 0 до `CHECKSUM_DEPTH` - 1 в строку дописываются "цена:объём"
+///i-го аска, затем i-го бида, если такие уровни есть, всё через ':'; аски идут по возрастанию цены,
+///биды по убыванию, числа - десятичные без ведущих нулей. От байт строки считается CRC32 IEEE 802.3
+///(как zlib.crc32), результат - беззнаковое 32-битное число. Пример: аски 101x5, биды 99x3 и 98x1
+///дают строку "101:5:99:3:98:1" и сумму 2769612461, пустой стакан - пустую строку и 0
+pub fn depth_checksum(
+    asks: impl Iterator<Item = (u32, u32)>,
+    bids: impl Iterator<Item = (u32, u32)>,
+) -> u32 {
+    let mut asks = asks.take(CHECKSUM_DEPTH);
+    let mut bids = bids.take(CHECKSUM_DEPTH);
+    let mut levels = Vec::new();
+    loop {
+        let (ask, bid) = (asks.next(), bids.next());
+        if ask.is_none() && bid.is_none() {
+            break;
+        }
+        levels.extend(
+            ask.into_iter()
+                .chain(bid)
+                .map(|(price, quantity)| format!("{price}:{quantity}")),
+        );
+    }
+    crc32fast::hash(levels.join(":").as_bytes())
+}
+
+impl DepthOfMarket {
+    ///сумма по уровням самого снимка: у представления с depth или group - по его уровням
+    #[must_use]
+    pub fn checksum(&self) -> u32 {
+        depth_checksum(
+            self.ask.iter().map(|entry| (entry.price, entry.quantity)),
+            self.bid.iter().map(|entry| (entry.price, entry.quantity)),
+        )
+    }
+
+    #[must_use]
+    pub fn view(&self, query: &DepthQuery) -> Self {
+        if query.is_full() {
+            return self.clone();
+        }
+        Self {
+            ask: query.levels(&OrderSide::Ask, self.ask.iter().cloned()),
+            bid: query.levels(&OrderSide::Bid, self.bid.iter().cloned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(levels: &[(u32, u32, u32)]) -> Vec<DepthEntry> {
+        levels
+            .iter()
+            .map(|&(price, quantity, orders)| DepthEntry {
+                price,
+                quantity,
+                orders,
+            })
+            .collect()
+    }
+
+    fn dom() -> DepthOfMarket {
+        DepthOfMarket {
+            ask: entries(&[(101, 1, 1), (102, 2, 1), (105, 3, 2), (111, 4, 1)]),
+            bid: entries(&[(99, 1, 1), (98, 2, 1), (95, 3, 2), (89, 4, 1)]),
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        let asks = [(101, 5)];
+        let bids = [(99, 3), (98, 1)];
+
+        assert_eq!(
+            depth_checksum(asks.into_iter(), bids.into_iter()),
+            2_769_612_461
+        );
+        assert_eq!(depth_checksum(std::iter::empty(), std::iter::empty()), 0);
+    }
+
+    #[test]
+    fn test_checksum_uses_top_levels_only() {
+        let mut asks: Vec<(u32, u32)> = (0..15).map(|i| (100 + i, 1)).collect();
+        let checksum =
+            |asks: &[(u32, u32)]| depth_checksum(asks.iter().copied(), std::iter::empty());
+        let original = checksum(&asks);
+
+        asks[CHECKSUM_DEPTH].1 = 7;
+        assert_eq!(checksum(&asks), original);
+
+        asks[CHECKSUM_DEPTH - 1].1 = 7;
+        assert_ne!(checksum(&asks), original);
+    }
+
+    #[test]
+    fn test_view_checksum() {
+        let query = DepthQuery {
+            depth: Some(1),
+            group: Some(10),
+        };
+
+        assert_eq!(
+            dom().view(&query).checksum(),
+            depth_checksum([(110, 6)].into_iter(), [(90, 6)].into_iter())
+        );
+    }
+
+    #[test]
+    fn test_full_view() {
+        assert_eq!(dom().view(&DepthQuery::default()), dom());
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let query = DepthQuery {
+            depth: Some(2),
+            group: None,
+        };
+
+        let view = dom().view(&query);
+
+        assert_eq!(view.ask, entries(&[(101, 1, 1), (102, 2, 1)]));
+        assert_eq!(view.bid, entries(&[(99, 1, 1), (98, 2, 1)]));
+    }
+
+    #[test]
+    fn test_grouping() {
+        let query = DepthQuery {
+            depth: None,
+            group: Some(5),
+        };
+
+        let view = dom().view(&query);
+
+        assert_eq!(view.ask, entries(&[(105, 6, 4), (115, 4, 1)]));
+        assert_eq!(view.bid, entries(&[(95, 6, 4), (85, 4, 1)]));
+    }
+
+    #[test]
+    fn test_grouping_with_depth_limit() {
+        let query = DepthQuery {
+            depth: Some(1),
+            group: Some(10),
+        };
+
+        let view = dom().view(&query);
+
+        assert_eq!(view.ask, entries(&[(110, 6, 4)]));
+        assert_eq!(view.bid, entries(&[(90, 6, 4)]));
+    }
+
+    #[test]
+    fn test_grouping_edge_cases() {
+        let query = DepthQuery {
+            depth: Some(0),
+            group: Some(1),
+        };
+        assert!(dom().view(&query).ask.is_empty());
+
+        let query = DepthQuery {
+            depth: None,
+            group: Some(10),
+        };
+        let max_dom = DepthOfMarket {
+            ask: entries(&[(u32::MAX, 1, 1)]),
+            bid: entries(&[(0, 1, 1)]),
+        };
+        let view = max_dom.view(&query);
+        assert_eq!(view.ask, entries(&[(u32::MAX, 1, 1)]));
+        assert_eq!(view.bid, entries(&[(0, 1, 1)]));
+    }
 }
@@ -0,0 +1,71 @@
+use crate::matching::models::account_book::AccountBalances;
+use crate::matching::models::cancel_filter::CancelFilter;
+use crate::matching::models::depth_of_market::DepthOfMarket;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::market_by_order::MarketByOrder;
+use crate::matching::models::mass_cancel_report::MassCancelReport;
+use crate::matching::models::order_message::OrderMessage;
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::order_status::OrderStatus;
+use crate::matching::models::reject_reason::RejectReason;
+use crate::matching::models::trade_history::{TradePage, TradesQuery};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+pub type OrderReply = oneshot::Sender<Result<OrderRecord, RejectReason>>;
+
+pub enum EngineCommand {
+    Place {
+        order: OrderMessage,
+        reply: OrderReply,
+    },
+    Cancel {
+        id: Uuid,
+        account: Option<String>,
+        reply: OrderReply,
+    },
+    Amend {
+        id: Uuid,
+        account: Option<String>,
+        price: Option<u32>,
+        quantity: Option<u32>,
+        reply: OrderReply,
+    },
+    GetOrder {
+        id: Uuid,
+        reply: oneshot::Sender<Option<OrderRecord>>,
+    },
+    GetOrders {
+        account: Option<String>,
+        status: Option<OrderStatus>,
+        reply: oneshot::Sender<Vec<OrderRecord>>,
+    },
+    GetOpenOrders {
+        account: String,
+        reply: oneshot::Sender<FeedMessage<Vec<OrderRecord>>>,
+    },
+    GetDepth {
+        reply: oneshot::Sender<FeedMessage<DepthOfMarket>>,
+    },
+    GetMarketByOrder {
+        reply: oneshot::Sender<FeedMessage<MarketByOrder>>,
+    },
+    Deposit {
+        account: String,
+        asset: String,
+        amount: u64,
+        reply: oneshot::Sender<Result<AccountBalances, RejectReason>>,
+    },
+    GetBalances {
+        account: String,
+        reply: oneshot::Sender<AccountBalances>,
+    },
+    GetTrades {
+        query: TradesQuery,
+        reply: oneshot::Sender<TradePage>,
+    },
+    MassCancel {
+        filter: CancelFilter,
+        reply: oneshot::Sender<MassCancelReport>,
+    },
+}
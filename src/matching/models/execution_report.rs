@@ -0,0 +1,87 @@
+use crate::matching::models::deal::Deal;
+use crate::matching::models::fee_schedule::Liquidity;
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::order_side::OrderSide;
+use crate::matching::models::order_status::OrderStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionKind {
+    Fill,
+    Cancel,
+    Amend,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub seq: u64, //сквозной номер отчёта в движке, назначается при публикации
+    pub kind: ExecutionKind,
+    pub order_id: Uuid,
+    pub account: Option<String>,
+    pub side: OrderSide,
+    pub price: u32,
+    pub quantity: u32,
+    pub remaining_quantity: u32,
+    pub status: OrderStatus,
+    pub last_price: Option<u32>,
+    pub last_quantity: Option<u32>,
+    pub trade_id: Option<u64>,
+    pub counter_order_id: Option<Uuid>,
+    pub liquidity: Option<Liquidity>,
+    pub fee: Option<u64>,
+    pub time: DateTime<Utc>,
+}
+
+impl ExecutionReport {
+    fn new(kind: ExecutionKind, order: &OrderRecord, time: DateTime<Utc>) -> Self {
+        Self {
+            seq: 0,
+            kind,
+            order_id: order.id,
+            account: order.account.clone(),
+            side: order.side.clone(),
+            price: order.price,
+            quantity: order.quantity,
+            remaining_quantity: order.remaining_quantity,
+            status: order.status,
+            last_price: None,
+            last_quantity: None,
+            trade_id: None,
+            counter_order_id: None,
+            liquidity: None,
+            fee: None,
+            time,
+        }
+    }
+
+    #[must_use]
+    pub fn fill(order: &OrderRecord, deal: &Deal, liquidity: Liquidity, fee: u64) -> Self {
+        let counter_order_id = match order.side {
+            OrderSide::Ask => deal.bid_order,
+            OrderSide::Bid => deal.ask_order,
+        };
+        Self {
+            last_price: Some(deal.price),
+            last_quantity: Some(deal.quantity),
+            trade_id: Some(deal.id),
+            counter_order_id: Some(counter_order_id),
+            liquidity: Some(liquidity),
+            fee: Some(fee),
+            ..Self::new(ExecutionKind::Fill, order, deal.time)
+        }
+    }
+
+    #[must_use]
+    pub fn cancel(order: &OrderRecord) -> Self {
+        Self::new(ExecutionKind::Cancel, order, Utc::now())
+    }
+
+    ///новые цена и количество ордера до исполнений, которые могло вызвать изменение
+    #[must_use]
+    pub fn amend(order: &OrderRecord) -> Self {
+        Self::new(ExecutionKind::Amend, order, Utc::now())
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+///maker - ордер стоял в книге, taker - пришёл и исполнился об неё
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+///ставки в базисных пунктах от объёма сделки price * quantity, комиссия округляется вверх
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FeeSchedule {
+    pub maker_bps: u32,
+    pub taker_bps: u32,
+}
+
+impl FeeSchedule {
+    #[must_use]
+    pub fn fee(self, liquidity: Liquidity, price: u32, quantity: u32) -> u64 {
+        let bps = match liquidity {
+            Liquidity::Maker => self.maker_bps,
+            Liquidity::Taker => self.taker_bps,
+        };
+        let fee = (u128::from(price) * u128::from(quantity) * u128::from(bps)).div_ceil(10_000);
+        u64::try_from(fee).unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_by_liquidity() {
+        let fees = FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 20,
+        };
+
+        assert_eq!(fees.fee(Liquidity::Maker, 500, 40), 20);
+        assert_eq!(fees.fee(Liquidity::Taker, 500, 40), 40);
+    }
+
+    #[test]
+    fn test_fee_rounds_up() {
+        let fees = FeeSchedule {
+            maker_bps: 1,
+            taker_bps: 1,
+        };
+
+        assert_eq!(fees.fee(Liquidity::Taker, 1, 1), 1);
+        assert_eq!(fees.fee(Liquidity::Taker, 0, 1), 0);
+        assert_eq!(
+            FeeSchedule::default().fee(Liquidity::Maker, u32::MAX, u32::MAX),
+            0
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedKind {
+    Update,
+    Snapshot,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedMessage<T> {
+    pub seq: u64,
+    #[serde(rename = "type")]
+    pub kind: FeedKind,
+    pub data: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>, //только у стакана, см. depth_checksum
+}
+
+impl<T> FeedMessage<T> {
+    pub const fn update(seq: u64, data: T) -> Self {
+        Self {
+            seq,
+            kind: FeedKind::Update,
+            data,
+            checksum: None,
+        }
+    }
+
+    pub const fn snapshot(seq: u64, data: T) -> Self {
+        Self {
+            seq,
+            kind: FeedKind::Snapshot,
+            data,
+            checksum: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_checksum(mut self, checksum: u32) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+}
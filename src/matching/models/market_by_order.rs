@@ -0,0 +1,37 @@
+use crate::matching::models::order_side::OrderSide;
+use serde::{Deserialize, Serialize};
+
+///очереди ордеров по ценам: аски по возрастанию цены, биды по убыванию, внутри цены по времени
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketByOrder {
+    pub ask: Vec<BookOrder>,
+    pub bid: Vec<BookOrder>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookOrder {
+    pub order_ref: u64,
+    pub price: u32,
+    pub quantity: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderEventKind {
+    Add,
+    Modify,
+    Execute,
+    Delete,
+}
+
+///add и modify несут новый остаток, execute - исполненное количество, delete - снятый остаток;
+///ордер, исполненный полностью, покидает очередь без отдельного delete
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    #[serde(rename = "type")]
+    pub kind: OrderEventKind,
+    pub order_ref: u64,
+    pub side: OrderSide,
+    pub price: u32,
+    pub quantity: u32,
+}
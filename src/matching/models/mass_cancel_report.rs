@@ -0,0 +1,18 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MassCancelReport {
+    pub count: usize,
+    pub canceled: Vec<Uuid>,
+}
+
+impl MassCancelReport {
+    #[must_use]
+    pub const fn new(canceled: Vec<Uuid>) -> Self {
+        Self {
+            count: canceled.len(),
+            canceled,
+        }
+    }
+}
@@ -1,8 +1,22 @@
-pub mod ask_order;
-pub mod bid_order;
+//! модели, видимые клиентам; книга ордеров, счета и команды движка в этом каталоге объявляет бинарник
+pub mod best_bid_offer;
+pub mod cancel_filter;
+pub mod candle;
 pub mod deal;
-pub mod dealbook;
 pub mod depth_of_market;
+pub mod execution_report;
+pub mod fee_schedule;
+pub mod feed_message;
+pub mod market_by_order;
+pub mod mass_cancel_report;
+pub mod order_ack;
 pub mod order_message;
+pub mod order_record;
 pub mod order_side;
-pub mod orderbook;
+pub mod order_status;
+pub mod reject_reason;
+pub mod subscription_message;
+pub mod ticker;
+pub mod trade_feed;
+pub mod trade_history;
+pub mod trade_message;
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OrderAck {
+    pub id: Uuid,
+    pub time: DateTime<Utc>,
+}
+
+impl OrderAck {
+    #[must_use]
+    pub fn new(id: Uuid) -> Self {
+        Self {
+            id,
+            time: Utc::now(),
+        }
+    }
+}
@@ -2,9 +2,11 @@ use crate::matching::models::order_side::OrderSide;
 use serde::Deserialize;
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 pub struct OrderMessage {
     pub id: Uuid,
+    #[serde(default)]
+    pub account: Option<String>,
     pub side: OrderSide,
     pub quantity: u32,
     pub price: u32,
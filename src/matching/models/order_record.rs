@@ -0,0 +1,127 @@
+use crate::matching::models::order_message::OrderMessage;
+use crate::matching::models::order_side::OrderSide;
+use crate::matching::models::order_status::OrderStatus;
+use crate::matching::models::reject_reason::RejectReason;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Fill {
+    pub time: DateTime<Utc>,
+    pub price: u32,
+    pub quantity: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderRecord {
+    pub id: Uuid,
+    pub account: Option<String>,
+    pub side: OrderSide,
+    pub price: u32,
+    pub quantity: u32,
+    pub remaining_quantity: u32,
+    pub status: OrderStatus,
+    pub fills: Vec<Fill>,
+}
+
+impl OrderRecord {
+    #[must_use]
+    pub fn new(order_message: &OrderMessage) -> Self {
+        Self {
+            id: order_message.id,
+            account: order_message.account.clone(),
+            side: order_message.side.clone(),
+            price: order_message.price,
+            quantity: order_message.quantity,
+            remaining_quantity: order_message.quantity,
+            status: if order_message.quantity > 0 {
+                OrderStatus::Open
+            } else {
+                OrderStatus::Filled
+            },
+            fills: Vec::new(),
+        }
+    }
+
+    ///новое количество задаётся на весь ордер, уже исполненная часть сохраняется
+    ///# Errors
+    ///`InvalidQuantity`, если новое количество не больше исполненного
+    pub const fn amend(&mut self, price: u32, quantity: u32) -> Result<(), RejectReason> {
+        let filled_quantity = self.quantity - self.remaining_quantity;
+        if quantity <= filled_quantity {
+            return Err(RejectReason::InvalidQuantity);
+        }
+        self.price = price;
+        self.quantity = quantity;
+        self.remaining_quantity = quantity - filled_quantity;
+        Ok(())
+    }
+
+    pub const fn cancel(&mut self) {
+        self.status = OrderStatus::Canceled;
+    }
+
+    pub fn fill(&mut self, time: DateTime<Utc>, price: u32, quantity: u32) {
+        self.remaining_quantity = self.remaining_quantity.saturating_sub(quantity);
+        if self.remaining_quantity == 0 {
+            self.status = OrderStatus::Filled;
+        }
+        self.fills.push(Fill {
+            time,
+            price,
+            quantity,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_record(quantity: u32) -> OrderRecord {
+        OrderRecord::new(&OrderMessage {
+            id: Uuid::new_v4(),
+            account: None,
+            side: OrderSide::Ask,
+            quantity,
+            price: 500,
+        })
+    }
+
+    #[test]
+    fn test_amend_keeps_filled_quantity() {
+        let mut order = order_record(100);
+        order.fill(Utc::now(), 500, 30);
+
+        order.amend(510, 50).unwrap();
+
+        assert_eq!(order.price, 510);
+        assert_eq!(order.quantity, 50);
+        assert_eq!(order.remaining_quantity, 20);
+        assert_eq!(order.status, OrderStatus::Open);
+    }
+
+    #[test]
+    fn test_amend_below_filled_quantity() {
+        let mut order = order_record(100);
+        order.fill(Utc::now(), 500, 30);
+
+        assert_eq!(order.amend(500, 30), Err(RejectReason::InvalidQuantity));
+        assert_eq!(order.quantity, 100);
+        assert_eq!(order.remaining_quantity, 70);
+    }
+
+    #[test]
+    fn test_fill_until_filled() {
+        let mut order = order_record(100);
+
+        order.fill(Utc::now(), 500, 60);
+        assert_eq!(order.status, OrderStatus::Open);
+
+        order.fill(Utc::now(), 500, 40);
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(order.remaining_quantity, 0);
+        assert_eq!(order.fills.len(), 2);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::matching::models::account_book::AccountBook;
+use crate::matching::models::cancel_filter::CancelFilter;
+use crate::matching::models::deal::Deal;
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::fee_schedule::Liquidity;
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::order_status::OrderStatus;
+use crate::matching::models::reject_reason::RejectReason;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use uuid::Uuid;
+
+struct StoredOrder {
+    seq: u64, //порядок создания, в нём отдаются списки ордеров
+    order: OrderRecord,
+}
+
+///открытые ордера проиндексированы по счетам; завершённые хранятся ограниченное число,
+///самые старые из них забываются
+pub struct OrderStore {
+    orders: HashMap<Uuid, StoredOrder>,
+    open: BTreeMap<u64, Uuid>,
+    open_by_account: HashMap<String, BTreeMap<u64, Uuid>>,
+    finished: VecDeque<Uuid>,
+    finished_capacity: usize,
+    next_seq: u64,
+    pub accounts: AccountBook,
+}
+
+impl OrderStore {
+    #[must_use]
+    pub fn new(accounts: AccountBook, finished_capacity: usize) -> Self {
+        Self {
+            orders: HashMap::new(),
+            open: BTreeMap::new(),
+            open_by_account: HashMap::new(),
+            finished: VecDeque::new(),
+            finished_capacity,
+            next_seq: 0,
+            accounts,
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.orders.contains_key(id)
+    }
+
+    ///повторная вставка заменяет ордер, сохраняя его место в порядке создания
+    pub fn insert(&mut self, order: OrderRecord) {
+        let seq = self.orders.get(&order.id).map_or_else(
+            || {
+                self.next_seq += 1;
+                self.next_seq
+            },
+            |stored| stored.seq,
+        );
+        let id = order.id;
+        let status = order.status;
+        if status == OrderStatus::Open {
+            self.open.insert(seq, id);
+            if let Some(account) = &order.account {
+                self.open_by_account
+                    .entry(account.clone())
+                    .or_default()
+                    .insert(seq, id);
+            }
+        }
+        self.orders.insert(id, StoredOrder { seq, order });
+        if status != OrderStatus::Open {
+            self.finish(id);
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &Uuid) -> Option<&OrderRecord> {
+        self.orders.get(id).map(|stored| &stored.order)
+    }
+
+    ///открытый ордер, которым может управлять указанный аккаунт
+    ///# Errors
+    ///`UnknownOrder` для чужого или неизвестного ордера, `OrderNotOpen` для завершённого
+    pub fn open_order(
+        &self,
+        id: &Uuid,
+        account: Option<&str>,
+    ) -> Result<&OrderRecord, RejectReason> {
+        let order = self
+            .get(id)
+            .filter(|order| account.is_none_or(|account| order.account.as_deref() == Some(account)))
+            .ok_or(RejectReason::UnknownOrder)?;
+        if order.status != OrderStatus::Open {
+            return Err(RejectReason::OrderNotOpen);
+        }
+        Ok(order)
+    }
+
+    #[must_use]
+    pub fn find(&self, account: Option<&str>, status: Option<OrderStatus>) -> Vec<OrderRecord> {
+        if status == Some(OrderStatus::Open) {
+            return self
+                .open_ids(account)
+                .filter_map(|id| self.get(&id))
+                .cloned()
+                .collect();
+        }
+        let mut orders: Vec<&StoredOrder> = self
+            .orders
+            .values()
+            .filter(|stored| {
+                account.is_none_or(|account| stored.order.account.as_deref() == Some(account))
+            })
+            .filter(|stored| status.is_none_or(|status| stored.order.status == status))
+            .collect();
+        orders.sort_unstable_by_key(|stored| stored.seq);
+        orders
+            .into_iter()
+            .map(|stored| stored.order.clone())
+            .collect()
+    }
+
+    ///отменяет открытый ордер и возвращает его резерв
+    ///# Errors
+    ///как у `open_order`
+    pub fn cancel_order(
+        &mut self,
+        id: &Uuid,
+        account: Option<&str>,
+    ) -> Result<ExecutionReport, RejectReason> {
+        self.open_order(id, account)?;
+        let stored = self.orders.get_mut(id).ok_or(RejectReason::UnknownOrder)?;
+        stored.order.cancel();
+        let report = ExecutionReport::cancel(&stored.order);
+        self.accounts.release(*id);
+        self.finish(*id);
+        Ok(report)
+    }
+
+    ///отменяет все открытые ордера под фильтром, возвращает их резервы; отчёты идут в порядке создания
+    pub fn cancel(&mut self, filter: &CancelFilter) -> Vec<ExecutionReport> {
+        let ids: Vec<Uuid> = self
+            .open_ids(filter.account.as_deref())
+            .filter(|id| self.get(id).is_some_and(|order| filter.matches(order)))
+            .collect();
+        ids.iter()
+            .filter_map(|id| self.cancel_order(id, None).ok())
+            .collect()
+    }
+
+    ///обновляет остатки и исполнения обоих ордеров каждой сделки и рассчитывает их счета;
+    ///комиссия зависит от того, чей ордер был агрессором
+    pub fn apply_deals(&mut self, deals: &[Deal]) -> Vec<ExecutionReport> {
+        let mut reports: Vec<ExecutionReport> = Vec::new();
+        for deal in deals {
+            for id in [deal.ask_order, deal.bid_order] {
+                if let Some(StoredOrder { order, .. }) = self.orders.get_mut(&id) {
+                    order.fill(deal.time, deal.price, deal.quantity);
+                    let liquidity = if order.side == deal.side {
+                        Liquidity::Taker
+                    } else {
+                        Liquidity::Maker
+                    };
+                    let fee = self.accounts.fee(liquidity, deal.price, deal.quantity);
+                    let report = ExecutionReport::fill(order, deal, liquidity, fee);
+                    self.accounts.settle(&report);
+                    if report.status != OrderStatus::Open {
+                        self.finish(id);
+                    }
+                    reports.push(report);
+                }
+            }
+        }
+        reports
+    }
+
+    fn open_ids(&self, account: Option<&str>) -> impl Iterator<Item = Uuid> + '_ {
+        account
+            .map_or(Some(&self.open), |account| {
+                self.open_by_account.get(account)
+            })
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .copied()
+    }
+
+    ///убирает ордер из индексов открытых и вытесняет самые старые завершённые сверх лимита
+    fn finish(&mut self, id: Uuid) {
+        let Some(stored) = self.orders.get(&id) else {
+            return;
+        };
+        self.open.remove(&stored.seq);
+        if let Some(account) = &stored.order.account
+            && let Some(open) = self.open_by_account.get_mut(account)
+        {
+            open.remove(&stored.seq);
+            if open.is_empty() {
+                self.open_by_account.remove(account);
+            }
+        }
+        self.finished.push_back(id);
+        while self.finished.len() > self.finished_capacity {
+            if let Some(evicted) = self.finished.pop_front() {
+                self.orders.remove(&evicted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::dealbook::DealBook;
+    use crate::matching::models::fee_schedule::FeeSchedule;
+    use crate::matching::models::order_message::OrderMessage;
+    use crate::matching::models::order_side::OrderSide;
+    use chrono::Utc;
+
+    fn order_store(fees: FeeSchedule) -> OrderStore {
+        let mut accounts = AccountBook::new("BTC", "USDT", fees);
+        accounts.deposit("alice", "BTC", 1_000).unwrap();
+        accounts.deposit("bob", "USDT", 1_000_000).unwrap();
+        OrderStore::new(accounts, 100)
+    }
+
+    ///ордер с резервом, как его выставляет движок
+    fn insert_reserved(store: &mut OrderStore, message: &OrderMessage) {
+        let order = OrderRecord::new(message);
+        store.accounts.reserve(&order).unwrap();
+        store.insert(order);
+    }
+
+    fn order_message(account: &str, side: OrderSide, quantity: u32) -> OrderMessage {
+        OrderMessage {
+            id: Uuid::new_v4(),
+            account: Some(account.to_owned()),
+            side,
+            quantity,
+            price: 500,
+        }
+    }
+
+    #[test]
+    fn test_insert_open_order() {
+        let mut store = order_store(FeeSchedule::default());
+        let message = order_message("alice", OrderSide::Ask, 100);
+
+        store.insert(OrderRecord::new(&message));
+
+        let order = store.get(&message.id).unwrap();
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(order.remaining_quantity, 100);
+        assert!(order.fills.is_empty());
+    }
+
+    #[test]
+    fn test_apply_deals_partial_and_full_fill() {
+        let mut store = order_store(FeeSchedule::default());
+        let ask = order_message("alice", OrderSide::Ask, 100);
+        let bid = order_message("bob", OrderSide::Bid, 40);
+        insert_reserved(&mut store, &ask);
+        insert_reserved(&mut store, &bid);
+
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        dealbook.push(OrderSide::Bid, 500, 40, ask.id, bid.id);
+        let reports = store.apply_deals(&dealbook.deals);
+        assert_eq!(reports.len(), 2);
+
+        let ask_order = store.get(&ask.id).unwrap();
+        assert_eq!(ask_order.status, OrderStatus::Open);
+        assert_eq!(ask_order.remaining_quantity, 60);
+        assert_eq!(ask_order.fills.len(), 1);
+
+        let bid_order = store.get(&bid.id).unwrap();
+        assert_eq!(bid_order.status, OrderStatus::Filled);
+        assert_eq!(bid_order.remaining_quantity, 0);
+        assert_eq!(bid_order.fills[0].quantity, 40);
+    }
+
+    #[test]
+    fn test_apply_deals_charges_maker_and_taker_fees() {
+        let mut store = order_store(FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 20,
+        });
+        let ask = order_message("alice", OrderSide::Ask, 100);
+        let bid = order_message("bob", OrderSide::Bid, 40);
+        insert_reserved(&mut store, &ask);
+        insert_reserved(&mut store, &bid);
+
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        dealbook.push(OrderSide::Bid, 500, 40, ask.id, bid.id);
+        let reports = store.apply_deals(&dealbook.deals);
+
+        let maker = reports
+            .iter()
+            .find(|report| report.order_id == ask.id)
+            .unwrap();
+        assert_eq!(maker.liquidity, Some(Liquidity::Maker));
+        assert_eq!(maker.fee, Some(20));
+        assert_eq!(maker.counter_order_id, Some(bid.id));
+        let taker = reports
+            .iter()
+            .find(|report| report.order_id == bid.id)
+            .unwrap();
+        assert_eq!(taker.liquidity, Some(Liquidity::Taker));
+        assert_eq!(taker.fee, Some(40));
+        assert_eq!(taker.trade_id, Some(1));
+    }
+
+    #[test]
+    fn test_find_by_account_and_status() {
+        let mut store = order_store(FeeSchedule::default());
+        let alice_open = order_message("alice", OrderSide::Ask, 100);
+        let alice_filled = order_message("alice", OrderSide::Ask, 0);
+        let bob_open = order_message("bob", OrderSide::Bid, 100);
+        store.insert(OrderRecord::new(&alice_open));
+        store.insert(OrderRecord::new(&alice_filled));
+        store.insert(OrderRecord::new(&bob_open));
+
+        let orders = store.find(Some("alice"), Some(OrderStatus::Open));
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, alice_open.id);
+
+        assert_eq!(store.find(Some("alice"), None).len(), 2);
+        assert_eq!(store.find(None, Some(OrderStatus::Open)).len(), 2);
+        assert!(store.find(Some("carol"), None).is_empty());
+    }
+
+    #[test]
+    fn test_open_order() {
+        let mut store = order_store(FeeSchedule::default());
+        let open = order_message("alice", OrderSide::Ask, 100);
+        let filled = order_message("alice", OrderSide::Ask, 0);
+        store.insert(OrderRecord::new(&open));
+        store.insert(OrderRecord::new(&filled));
+
+        assert!(store.open_order(&open.id, Some("alice")).is_ok());
+        assert!(store.open_order(&open.id, None).is_ok());
+        assert_eq!(
+            store.open_order(&open.id, Some("bob")).unwrap_err(),
+            RejectReason::UnknownOrder
+        );
+        assert_eq!(
+            store.open_order(&Uuid::new_v4(), None).unwrap_err(),
+            RejectReason::UnknownOrder
+        );
+        assert_eq!(
+            store.open_order(&filled.id, Some("alice")).unwrap_err(),
+            RejectReason::OrderNotOpen
+        );
+    }
+
+    #[test]
+    fn test_cancel_only_open_orders_under_filter() {
+        let mut store = order_store(FeeSchedule::default());
+        let alice_open = order_message("alice", OrderSide::Ask, 100);
+        let alice_filled = order_message("alice", OrderSide::Ask, 0);
+        let bob_open = order_message("bob", OrderSide::Bid, 100);
+        store.insert(OrderRecord::new(&alice_open));
+        store.insert(OrderRecord::new(&alice_filled));
+        store.insert(OrderRecord::new(&bob_open));
+
+        let filter = CancelFilter {
+            account: Some(String::from("alice")),
+            ..CancelFilter::default()
+        };
+        let canceled = store.cancel(&filter);
+
+        assert_eq!(canceled.len(), 1);
+        assert_eq!(canceled[0].order_id, alice_open.id);
+        assert_eq!(
+            store.get(&alice_open.id).unwrap().status,
+            OrderStatus::Canceled
+        );
+        assert_eq!(
+            store.get(&alice_filled.id).unwrap().status,
+            OrderStatus::Filled
+        );
+        assert_eq!(store.get(&bob_open.id).unwrap().status, OrderStatus::Open);
+        assert!(store.cancel(&filter).is_empty());
+    }
+
+    #[test]
+    fn test_find_in_creation_order() {
+        let mut store = order_store(FeeSchedule::default());
+        let messages: Vec<OrderMessage> = (1..=5)
+            .map(|quantity| order_message("alice", OrderSide::Ask, quantity))
+            .collect();
+        for message in &messages {
+            store.insert(OrderRecord::new(message));
+        }
+        store.cancel_order(&messages[1].id, None).unwrap();
+        let mut amended = store.get(&messages[0].id).unwrap().clone();
+        amended.amend(500, 10).unwrap();
+        store.insert(amended);
+
+        let ids = |orders: Vec<OrderRecord>| -> Vec<Uuid> {
+            orders.into_iter().map(|order| order.id).collect()
+        };
+        let all: Vec<Uuid> = messages.iter().map(|message| message.id).collect();
+        assert_eq!(ids(store.find(Some("alice"), None)), all);
+        assert_eq!(
+            ids(store.find(Some("alice"), Some(OrderStatus::Open))),
+            vec![all[0], all[2], all[3], all[4]]
+        );
+        assert_eq!(
+            ids(store.find(None, Some(OrderStatus::Canceled))),
+            vec![all[1]]
+        );
+    }
+
+    #[test]
+    fn test_finished_orders_are_bounded() {
+        let mut store = OrderStore::new(AccountBook::new("BTC", "USDT", FeeSchedule::default()), 2);
+        let open = order_message("alice", OrderSide::Ask, 100);
+        store.insert(OrderRecord::new(&open));
+        let finished: Vec<OrderMessage> = (0..3)
+            .map(|_| order_message("alice", OrderSide::Ask, 0))
+            .collect();
+        for message in &finished {
+            store.insert(OrderRecord::new(message));
+        }
+
+        assert!(store.get(&finished[0].id).is_none());
+        assert!(store.get(&finished[2].id).is_some());
+        assert!(store.get(&open.id).is_some());
+        assert_eq!(store.find(Some("alice"), None).len(), 3);
+
+        store.cancel_order(&open.id, Some("alice")).unwrap();
+        assert!(store.get(&finished[1].id).is_none());
+        assert!(
+            store
+                .find(Some("alice"), Some(OrderStatus::Open))
+                .is_empty()
+        );
+        assert!(store.open_by_account.is_empty());
+    }
+}
@@ -1,68 +1,149 @@
-use crate::DepthOfMarket;
 use crate::matching::models::ask_order::AskOrder;
+use crate::matching::models::best_bid_offer::BestBidOffer;
 use crate::matching::models::bid_order::BidOrder;
+use crate::matching::models::deal::Deal;
 use crate::matching::models::dealbook::DealBook;
-use crate::matching::models::depth_of_market::DepthEntry;
+use crate::matching::models::depth_of_market::DepthOfMarket;
+use crate::matching::models::depth_of_market::{DepthChange, DepthEntry, depth_checksum};
+use crate::matching::models::market_by_order::{
+    BookOrder, MarketByOrder, OrderEvent, OrderEventKind,
+};
+use crate::matching::models::order_side::OrderSide;
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
 
 pub struct OrderBook {
-    pub asks_book: HashMap<u32, u32>, //используется для быстрого показа стакана
-    pub bids_book: HashMap<u32, u32>,
+    pub asks_book: BTreeMap<u32, u32>, //упорядочен по цене: верхние уровни берутся без сортировки
+    pub bids_book: BTreeMap<u32, u32>,
+    pub asks_orders: HashMap<u32, u32>, //количество ордеров на уровне, для оценки места в очереди
+    pub bids_orders: HashMap<u32, u32>,
     pub asks: BinaryHeap<AskOrder>, //используется для быстрого исполнения сделок
     pub bids: BinaryHeap<BidOrder>,
+    changed_asks: BTreeSet<u32>, //уровни, изменившиеся с последней публикации
+    changed_bids: BTreeSet<u32>,
+    order_events: Vec<OrderEvent>, //события по ордерам с последней публикации
+    next_order_ref: u64,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OrderBook {
+    #[must_use]
     pub fn new() -> Self {
         Self {
-            asks_book: HashMap::new(),
-            bids_book: HashMap::new(),
+            asks_book: BTreeMap::new(),
+            bids_book: BTreeMap::new(),
+            asks_orders: HashMap::new(),
+            bids_orders: HashMap::new(),
             asks: BinaryHeap::new(),
             bids: BinaryHeap::new(),
+            changed_asks: BTreeSet::new(),
+            changed_bids: BTreeSet::new(),
+            order_events: Vec::new(),
+            next_order_ref: 1,
         }
     }
 
     fn add_asks_book_quantity(&mut self, deal_quantity: u32, price: u32) {
+        self.changed_asks.insert(price);
         let entry = self.asks_book.entry(price).or_insert(0);
         *entry += deal_quantity;
+        *self.asks_orders.entry(price).or_insert(0) += 1;
     }
 
     fn add_bids_book_quantity(&mut self, deal_quantity: u32, price: u32) {
+        self.changed_bids.insert(price);
         let entry = self.bids_book.entry(price).or_insert(0);
         *entry += deal_quantity;
+        *self.bids_orders.entry(price).or_insert(0) += 1;
     }
 
     fn subtract_asks_book_quantity(&mut self, deal_quantity: u32, price: u32) {
+        self.changed_asks.insert(price);
         if let Some(quantity) = self.asks_book.get_mut(&price) {
             if *quantity <= deal_quantity {
                 self.asks_book.remove(&price);
+                self.asks_orders.remove(&price);
             } else {
                 *quantity -= deal_quantity;
+                if let Some(orders) = self.asks_orders.get_mut(&price) {
+                    *orders -= 1;
+                }
             }
         }
     }
 
     fn subtract_bids_book_quantity(&mut self, deal_quantity: u32, price: u32) {
+        self.changed_bids.insert(price);
         if let Some(quantity) = self.bids_book.get_mut(&price) {
             if *quantity <= deal_quantity {
                 self.bids_book.remove(&price);
+                self.bids_orders.remove(&price);
             } else {
                 *quantity -= deal_quantity;
+                if let Some(orders) = self.bids_orders.get_mut(&price) {
+                    *orders -= 1;
+                }
             }
         }
     }
 
-    pub fn asks_push(&mut self, ask_order: AskOrder) {
+    fn record_order_event(
+        &mut self,
+        kind: OrderEventKind,
+        order_ref: u64,
+        side: OrderSide,
+        price: u32,
+        quantity: u32,
+    ) {
+        self.order_events.push(OrderEvent {
+            kind,
+            order_ref,
+            side,
+            price,
+            quantity,
+        });
+    }
+
+    const fn take_order_ref(&mut self) -> u64 {
+        let order_ref = self.next_order_ref;
+        self.next_order_ref += 1;
+        order_ref
+    }
+
+    pub fn asks_push(&mut self, mut ask_order: AskOrder) {
         if ask_order.current_quantity > 0 {
+            ask_order.order_ref = self.take_order_ref();
             Self::add_asks_book_quantity(self, ask_order.current_quantity, ask_order.price);
+            self.record_order_event(
+                OrderEventKind::Add,
+                ask_order.order_ref,
+                OrderSide::Ask,
+                ask_order.price,
+                ask_order.current_quantity,
+            );
             self.asks.push(ask_order);
         }
     }
 
-    pub fn bids_push(&mut self, bid_order: BidOrder) {
+    pub fn bids_push(&mut self, mut bid_order: BidOrder) {
         if bid_order.current_quantity > 0 {
+            bid_order.order_ref = self.take_order_ref();
             Self::add_bids_book_quantity(self, bid_order.current_quantity, bid_order.price);
+            self.record_order_event(
+                OrderEventKind::Add,
+                bid_order.order_ref,
+                OrderSide::Bid,
+                bid_order.price,
+                bid_order.current_quantity,
+            );
             self.bids.push(bid_order);
         }
     }
@@ -71,7 +152,15 @@ impl OrderBook {
     pub fn asks_pop(&mut self, bid_order: BidOrder, dealbook: &mut DealBook) -> BidOrder {
         if let Some(ask_order) = self.asks.pop() {
             Self::subtract_asks_book_quantity(self, ask_order.current_quantity, ask_order.price);
+            self.record_order_event(
+                OrderEventKind::Execute,
+                ask_order.order_ref,
+                OrderSide::Ask,
+                ask_order.price,
+                ask_order.current_quantity,
+            );
             dealbook.push(
+                OrderSide::Bid,
                 ask_order.price,
                 ask_order.current_quantity,
                 ask_order.id,
@@ -92,7 +181,15 @@ impl OrderBook {
     pub fn bids_pop(&mut self, ask_order: AskOrder, dealbook: &mut DealBook) -> AskOrder {
         if let Some(bid_order) = self.bids.pop() {
             Self::subtract_bids_book_quantity(self, bid_order.current_quantity, bid_order.price);
+            self.record_order_event(
+                OrderEventKind::Execute,
+                bid_order.order_ref,
+                OrderSide::Bid,
+                bid_order.price,
+                bid_order.current_quantity,
+            );
             dealbook.push(
+                OrderSide::Ask,
                 bid_order.price,
                 bid_order.current_quantity,
                 ask_order.id,
@@ -113,12 +210,21 @@ impl OrderBook {
     pub fn asks_peek_mut(&mut self, bid_order: BidOrder, dealbook: &mut DealBook) -> BidOrder {
         if let Some(mut ask_order) = self.asks.peek_mut() {
             ask_order.current_quantity -= bid_order.current_quantity;
+            self.changed_asks.insert(ask_order.price);
 
             if let Some(quantity) = self.asks_book.get_mut(&ask_order.price) {
                 *quantity -= bid_order.current_quantity;
             }
+            self.order_events.push(OrderEvent {
+                kind: OrderEventKind::Execute,
+                order_ref: ask_order.order_ref,
+                side: OrderSide::Ask,
+                price: ask_order.price,
+                quantity: bid_order.current_quantity,
+            });
 
             dealbook.push(
+                OrderSide::Bid,
                 ask_order.price,
                 bid_order.current_quantity,
                 ask_order.id,
@@ -135,12 +241,21 @@ impl OrderBook {
     pub fn bids_peek_mut(&mut self, ask_order: AskOrder, dealbook: &mut DealBook) -> AskOrder {
         if let Some(mut bid_order) = self.bids.peek_mut() {
             bid_order.current_quantity -= ask_order.current_quantity;
+            self.changed_bids.insert(bid_order.price);
 
             if let Some(quantity) = self.bids_book.get_mut(&bid_order.price) {
                 *quantity -= ask_order.current_quantity;
             }
+            self.order_events.push(OrderEvent {
+                kind: OrderEventKind::Execute,
+                order_ref: bid_order.order_ref,
+                side: OrderSide::Bid,
+                price: bid_order.price,
+                quantity: ask_order.current_quantity,
+            });
 
             dealbook.push(
+                OrderSide::Ask,
                 bid_order.price,
                 ask_order.current_quantity,
                 ask_order.id,
@@ -152,19 +267,216 @@ impl OrderBook {
         ask_order
     }
 
+    ///снимает с книги все ордера с указанными id за один проход по каждой очереди
+    pub fn remove_orders(&mut self, ids: &HashSet<Uuid>) {
+        let mut removed_asks: Vec<(u64, u32, u32)> = Vec::new();
+        self.asks.retain(|ask_order| {
+            let keep = !ids.contains(&ask_order.id);
+            if !keep {
+                removed_asks.push((
+                    ask_order.order_ref,
+                    ask_order.current_quantity,
+                    ask_order.price,
+                ));
+            }
+            keep
+        });
+        for (order_ref, quantity, price) in removed_asks {
+            Self::subtract_asks_book_quantity(self, quantity, price);
+            self.record_order_event(
+                OrderEventKind::Delete,
+                order_ref,
+                OrderSide::Ask,
+                price,
+                quantity,
+            );
+        }
+
+        let mut removed_bids: Vec<(u64, u32, u32)> = Vec::new();
+        self.bids.retain(|bid_order| {
+            let keep = !ids.contains(&bid_order.id);
+            if !keep {
+                removed_bids.push((
+                    bid_order.order_ref,
+                    bid_order.current_quantity,
+                    bid_order.price,
+                ));
+            }
+            keep
+        });
+        for (order_ref, quantity, price) in removed_bids {
+            Self::subtract_bids_book_quantity(self, quantity, price);
+            self.record_order_event(
+                OrderEventKind::Delete,
+                order_ref,
+                OrderSide::Bid,
+                price,
+                quantity,
+            );
+        }
+    }
+
+    ///уменьшает остаток ордера, не меняя его места в очереди
+    pub fn reduce_order(&mut self, id: Uuid, side: &OrderSide, quantity: u32) {
+        let reduced = match side {
+            OrderSide::Ask => {
+                let mut asks = std::mem::take(&mut self.asks).into_vec();
+                let reduced =
+                    asks.iter_mut()
+                        .find(|ask_order| ask_order.id == id)
+                        .map(|ask_order| {
+                            let delta = ask_order.current_quantity.saturating_sub(quantity);
+                            ask_order.current_quantity -= delta;
+                            (ask_order.order_ref, ask_order.price, delta)
+                        });
+                self.asks = asks.into();
+                reduced.map(|(order_ref, price, delta)| {
+                    self.changed_asks.insert(price);
+                    if let Some(level) = self.asks_book.get_mut(&price) {
+                        *level -= delta;
+                    }
+                    (order_ref, price)
+                })
+            }
+            OrderSide::Bid => {
+                let mut bids = std::mem::take(&mut self.bids).into_vec();
+                let reduced =
+                    bids.iter_mut()
+                        .find(|bid_order| bid_order.id == id)
+                        .map(|bid_order| {
+                            let delta = bid_order.current_quantity.saturating_sub(quantity);
+                            bid_order.current_quantity -= delta;
+                            (bid_order.order_ref, bid_order.price, delta)
+                        });
+                self.bids = bids.into();
+                reduced.map(|(order_ref, price, delta)| {
+                    self.changed_bids.insert(price);
+                    if let Some(level) = self.bids_book.get_mut(&price) {
+                        *level -= delta;
+                    }
+                    (order_ref, price)
+                })
+            }
+        };
+
+        if let Some((order_ref, price)) = reduced {
+            self.record_order_event(
+                OrderEventKind::Modify,
+                order_ref,
+                side.clone(),
+                price,
+                quantity,
+            );
+        }
+    }
+
+    ///изменения уровней с прошлого вызова: аски по возрастанию цены, биды по убыванию
+    pub fn take_depth_changes(&mut self) -> Vec<DepthChange> {
+        let asks = std::mem::take(&mut self.changed_asks)
+            .into_iter()
+            .map(|price| DepthChange {
+                side: OrderSide::Ask,
+                price,
+                quantity: self.asks_book.get(&price).copied().unwrap_or(0),
+                orders: self.asks_orders.get(&price).copied().unwrap_or(0),
+            });
+        let bids = std::mem::take(&mut self.changed_bids)
+            .into_iter()
+            .rev()
+            .map(|price| DepthChange {
+                side: OrderSide::Bid,
+                price,
+                quantity: self.bids_book.get(&price).copied().unwrap_or(0),
+                orders: self.bids_orders.get(&price).copied().unwrap_or(0),
+            });
+        asks.chain(bids).collect()
+    }
+
+    ///события по ордерам с прошлого вызова в порядке их возникновения
+    pub fn take_order_events(&mut self) -> Vec<OrderEvent> {
+        std::mem::take(&mut self.order_events)
+    }
+
+    #[must_use]
+    pub fn get_market_by_order(&self) -> MarketByOrder {
+        MarketByOrder {
+            ask: self
+                .asks
+                .clone()
+                .into_sorted_vec()
+                .into_iter()
+                .rev()
+                .map(|ask_order| BookOrder {
+                    order_ref: ask_order.order_ref,
+                    price: ask_order.price,
+                    quantity: ask_order.current_quantity,
+                })
+                .collect(),
+            bid: self
+                .bids
+                .clone()
+                .into_sorted_vec()
+                .into_iter()
+                .rev()
+                .map(|bid_order| BookOrder {
+                    order_ref: bid_order.order_ref,
+                    price: bid_order.price,
+                    quantity: bid_order.current_quantity,
+                })
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn get_best_bid_offer(&self, last_deal: Option<&Deal>) -> BestBidOffer {
+        let ask_price = self.asks.peek().map(|ask_order| ask_order.price);
+        let bid_price = self.bids.peek().map(|bid_order| bid_order.price);
+        BestBidOffer {
+            bid_price,
+            bid_quantity: bid_price.and_then(|price| self.bids_book.get(&price).copied()),
+            ask_price,
+            ask_quantity: ask_price.and_then(|price| self.asks_book.get(&price).copied()),
+            last_price: last_deal.map(|deal| deal.price),
+            last_quantity: last_deal.map(|deal| deal.quantity),
+        }
+    }
+
+    ///контрольная сумма верхних уровней по правилам `depth_checksum`
+    #[must_use]
+    pub fn get_checksum(&self) -> u32 {
+        depth_checksum(
+            self.asks_book
+                .iter()
+                .map(|(&price, &quantity)| (price, quantity)),
+            self.bids_book
+                .iter()
+                .rev()
+                .map(|(&price, &quantity)| (price, quantity)),
+        )
+    }
+
+    ///аски по возрастанию цены, биды по убыванию
+    #[must_use]
     pub fn get_dom(&self) -> DepthOfMarket {
-        let ask: Vec<DepthEntry> = self
+        let ask = self
             .asks_book
             .iter()
-            .map(|(&price, &quantity)| DepthEntry { price, quantity })
+            .map(|(&price, &quantity)| DepthEntry {
+                price,
+                quantity,
+                orders: self.asks_orders.get(&price).copied().unwrap_or(0),
+            })
             .collect();
-
-        let bid: Vec<DepthEntry> = self
+        let bid = self
             .bids_book
             .iter()
-            .map(|(&price, &quantity)| DepthEntry { price, quantity })
+            .rev()
+            .map(|(&price, &quantity)| DepthEntry {
+                price,
+                quantity,
+                orders: self.bids_orders.get(&price).copied().unwrap_or(0),
+            })
             .collect();
-
         DepthOfMarket { ask, bid }
     }
 }
@@ -174,6 +486,8 @@ mod tests {
     use super::*;
     use crate::matching::models::ask_order::AskOrder;
     use crate::matching::models::bid_order::BidOrder;
+    use crate::matching::models::depth_book::DepthBook;
+    use chrono::Utc;
     use uuid::Uuid;
 
     // классы эквивалентности  asks_push, bids_push
@@ -276,7 +590,7 @@ mod tests {
     #[test]
     fn test_asks_pop_empty_asks_queue() {
         let mut order_book = OrderBook::new();
-        let mut dealbook = DealBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
         let bid_id = Uuid::new_v4();
         let bid_order = BidOrder::new(bid_id, 100, 50, 500);
         let result_order = order_book.asks_pop(bid_order, &mut dealbook);
@@ -290,7 +604,7 @@ mod tests {
     #[test]
     fn test_asks_pop_non_empty_asks_queue() {
         let mut order_book = OrderBook::new();
-        let mut dealbook = DealBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
         let bid_id = Uuid::new_v4();
         let price: u32 = 500;
         let ask_order = AskOrder::new(bid_id, 100, 50, price);
@@ -311,7 +625,7 @@ mod tests {
     #[test]
     fn test_bids_pop_empty_bids_queue() {
         let mut order_book = OrderBook::new();
-        let mut dealbook = DealBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
         let ask_id = Uuid::new_v4();
         let ask_order = AskOrder::new(ask_id, 100, 50, 500);
         let result_order = order_book.bids_pop(ask_order, &mut dealbook);
@@ -325,7 +639,7 @@ mod tests {
     #[test]
     fn test_bids_pop_non_empty_bids_queue() {
         let mut order_book = OrderBook::new();
-        let mut dealbook = DealBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
         let ask_id = Uuid::new_v4();
         let bid_order = BidOrder::new(ask_id, 100, 50, 500);
         order_book.bids_push(bid_order);
@@ -342,11 +656,101 @@ mod tests {
         assert!(!dealbook.deals.is_empty());
     }
 
+    #[test]
+    fn test_remove_orders() {
+        let mut orderbook = OrderBook::new();
+        let ask_id = Uuid::new_v4();
+        let bid_id = Uuid::new_v4();
+        let kept_ask_id = Uuid::new_v4();
+        orderbook.asks_push(AskOrder::new(ask_id, 100, 40, 510));
+        orderbook.asks_push(AskOrder::new(kept_ask_id, 100, 60, 510));
+        orderbook.bids_push(BidOrder::new(bid_id, 70, 70, 490));
+
+        orderbook.remove_orders(&HashSet::from([ask_id, bid_id]));
+
+        assert_eq!(orderbook.asks.len(), 1);
+        assert_eq!(orderbook.asks.peek().unwrap().id, kept_ask_id);
+        assert_eq!(orderbook.asks_book.get(&510).unwrap(), &60);
+        assert!(orderbook.bids.is_empty());
+        assert!(orderbook.bids_book.is_empty());
+    }
+
+    #[test]
+    fn test_take_depth_changes() {
+        let mut orderbook = OrderBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 50, 50, 500));
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 100, 100, 510));
+        orderbook.bids_push(BidOrder::new(Uuid::new_v4(), 30, 30, 480));
+        orderbook.bids_push(BidOrder::new(Uuid::new_v4(), 70, 70, 490));
+
+        let changes = orderbook.take_depth_changes();
+        let levels: Vec<(OrderSide, u32, u32)> = changes
+            .into_iter()
+            .map(|change| (change.side, change.price, change.quantity))
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                (OrderSide::Ask, 500, 50),
+                (OrderSide::Ask, 510, 100),
+                (OrderSide::Bid, 490, 70),
+                (OrderSide::Bid, 480, 30),
+            ]
+        );
+        assert!(orderbook.take_depth_changes().is_empty());
+
+        orderbook.asks_pop(BidOrder::new(Uuid::new_v4(), 50, 50, 500), &mut dealbook);
+        orderbook.asks_peek_mut(BidOrder::new(Uuid::new_v4(), 40, 40, 510), &mut dealbook);
+
+        let changes = orderbook.take_depth_changes();
+        assert_eq!(
+            changes,
+            vec![
+                DepthChange {
+                    side: OrderSide::Ask,
+                    price: 500,
+                    quantity: 0,
+                    orders: 0,
+                },
+                DepthChange {
+                    side: OrderSide::Ask,
+                    price: 510,
+                    quantity: 60,
+                    orders: 1,
+                },
+            ]
+        );
+    }
+
     //Классы эквивалентности asks_peek_mut, bids_peek_mut
     // Количество:
     // 1. Очередь asks/bids пуста
     // 2. Очередь asks/bids непуста
 
+    #[test]
+    fn test_checksum_matches_book_built_from_changes() {
+        let mut orderbook = OrderBook::new();
+        let mut book = DepthBook::new(&orderbook.get_dom());
+        assert_eq!(book.checksum(), orderbook.get_checksum());
+
+        for i in 0..12 {
+            orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 10, 10 + i, 500 + i));
+            orderbook.bids_push(BidOrder::new(Uuid::new_v4(), 10, 10 + i, 490 - i));
+        }
+        book.apply(&orderbook.take_depth_changes());
+        assert_eq!(book.checksum(), orderbook.get_checksum());
+        assert_eq!(
+            DepthBook::new(&orderbook.get_dom()).checksum(),
+            orderbook.get_checksum()
+        );
+
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        orderbook.asks_pop(BidOrder::new(Uuid::new_v4(), 10, 10, 500), &mut dealbook);
+        book.apply(&orderbook.take_depth_changes());
+        assert_eq!(book.checksum(), orderbook.get_checksum());
+    }
+
     #[test]
     fn test_get_dom() {
         let mut orderbook = OrderBook::new();
@@ -368,6 +772,9 @@ mod tests {
         assert!(bid_prices.contains(&480));
         assert!(bid_prices.contains(&490));
 
+        assert_eq!(ask_prices, vec![500, 510]);
+        assert_eq!(bid_prices, vec![490, 480]);
+
         for entry in dom.ask {
             if entry.price == 500 {
                 assert_eq!(entry.quantity, 50);
@@ -384,4 +791,125 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_order_counts_per_level() {
+        let mut orderbook = OrderBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        let removed_id = Uuid::new_v4();
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 10, 10, 500));
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 10, 10, 500));
+        orderbook.asks_push(AskOrder::new(removed_id, 30, 30, 510));
+        orderbook.bids_push(BidOrder::new(Uuid::new_v4(), 5, 5, 490));
+        assert_eq!(orderbook.asks_orders.get(&500).unwrap(), &2);
+
+        orderbook.asks_peek_mut(BidOrder::new(Uuid::new_v4(), 4, 4, 500), &mut dealbook);
+        assert_eq!(orderbook.asks_orders.get(&500).unwrap(), &2);
+
+        orderbook.asks_pop(BidOrder::new(Uuid::new_v4(), 10, 10, 500), &mut dealbook);
+        orderbook.remove_orders(&HashSet::from([removed_id]));
+
+        let dom = orderbook.get_dom();
+        assert_eq!(dom.ask.len(), 1);
+        assert_eq!(dom.ask[0].orders, 1);
+        assert_eq!(dom.bid[0].orders, 1);
+        assert!(!orderbook.asks_orders.contains_key(&510));
+    }
+
+    #[test]
+    fn test_order_events_follow_queue() {
+        let mut orderbook = OrderBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        let first_id = Uuid::new_v4();
+        orderbook.asks_push(AskOrder::new(first_id, 10, 10, 500));
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 20, 20, 500));
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 30, 30, 490));
+
+        orderbook.asks_pop(BidOrder::new(Uuid::new_v4(), 30, 30, 500), &mut dealbook);
+        orderbook.asks_peek_mut(BidOrder::new(Uuid::new_v4(), 4, 4, 500), &mut dealbook);
+        orderbook.reduce_order(first_id, &OrderSide::Ask, 5);
+
+        let events: Vec<(OrderEventKind, u64, u32)> = orderbook
+            .take_order_events()
+            .into_iter()
+            .map(|event| (event.kind, event.order_ref, event.quantity))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (OrderEventKind::Add, 1, 10),
+                (OrderEventKind::Add, 2, 20),
+                (OrderEventKind::Add, 3, 30),
+                (OrderEventKind::Execute, 3, 30),
+                (OrderEventKind::Execute, 1, 4),
+                (OrderEventKind::Modify, 1, 5),
+            ]
+        );
+        assert!(orderbook.take_order_events().is_empty());
+
+        let queue = orderbook.get_market_by_order();
+        assert_eq!(
+            queue.ask,
+            vec![
+                BookOrder {
+                    order_ref: 1,
+                    price: 500,
+                    quantity: 5,
+                },
+                BookOrder {
+                    order_ref: 2,
+                    price: 500,
+                    quantity: 20,
+                },
+            ]
+        );
+        assert_eq!(orderbook.asks_book.get(&500).unwrap(), &25);
+    }
+
+    #[test]
+    fn test_removed_orders_produce_delete_events() {
+        let mut orderbook = OrderBook::new();
+        let bid_id = Uuid::new_v4();
+        orderbook.bids_push(BidOrder::new(bid_id, 70, 70, 490));
+        orderbook.take_order_events();
+
+        orderbook.remove_orders(&HashSet::from([bid_id]));
+
+        assert_eq!(
+            orderbook.take_order_events(),
+            vec![OrderEvent {
+                kind: OrderEventKind::Delete,
+                order_ref: 1,
+                side: OrderSide::Bid,
+                price: 490,
+                quantity: 70,
+            }]
+        );
+        assert!(orderbook.get_market_by_order().bid.is_empty());
+    }
+
+    #[test]
+    fn test_get_best_bid_offer() {
+        let mut orderbook = OrderBook::new();
+        let mut dealbook = DealBook::new(1, 1, Utc::now());
+        assert_eq!(orderbook.get_best_bid_offer(None), BestBidOffer::default());
+
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 10, 10, 510));
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 20, 20, 510));
+        orderbook.asks_push(AskOrder::new(Uuid::new_v4(), 30, 30, 520));
+        orderbook.bids_push(BidOrder::new(Uuid::new_v4(), 5, 5, 490));
+        orderbook.asks_peek_mut(BidOrder::new(Uuid::new_v4(), 4, 4, 510), &mut dealbook);
+
+        assert_eq!(
+            orderbook.get_best_bid_offer(dealbook.deals.last()),
+            BestBidOffer {
+                bid_price: Some(490),
+                bid_quantity: Some(5),
+                ask_price: Some(510),
+                ask_quantity: Some(26),
+                last_price: Some(510),
+                last_quantity: Some(4),
+            }
+        );
+    }
 }
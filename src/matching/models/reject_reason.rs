@@ -0,0 +1,15 @@
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    InvalidMessage,
+    InvalidQuantity,
+    DuplicateOrderId,
+    UnknownOrder,
+    OrderNotOpen,
+    EngineUnavailable,
+    InsufficientFunds,
+    UnknownAsset,
+    MissingAccount,
+}
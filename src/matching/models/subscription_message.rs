@@ -0,0 +1,253 @@
+use crate::matching::models::candle::CandleInterval;
+use crate::matching::models::depth_of_market::DepthQuery;
+use crate::matching::models::feed_message::FeedMessage;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Depth,
+    Trades,
+    Bbo,
+    MarketByOrder,
+    Orders,
+    Fills,
+    Candles,
+    Ticker,
+}
+
+///рыночные каналы адресуются инструментом, приватные каналы orders и fills - счётом.
+///
+///history - сколько последних сделок прислать в снимке канала trades,
+///`conflation_ms` - не чаще одного кадра depth за интервал; канал candles адресуется инструментом и интервалом,
+///history у него - сколько последних баров прислать в снимке
+#[derive(Clone, Debug, Deserialize)]
+pub struct Subscription {
+    pub channel: Channel,
+    pub instrument: Option<String>,
+    pub account: Option<String>,
+    pub history: Option<usize>,
+    pub conflation_ms: Option<u64>,
+    pub interval: Option<CandleInterval>,
+    #[serde(flatten)]
+    pub depth: DepthQuery,
+}
+
+impl Subscription {
+    pub fn conflation(&self) -> Option<Duration> {
+        self.conflation_ms
+            .filter(|&millis| millis > 0)
+            .map(Duration::from_millis)
+    }
+
+    ///# Errors
+    ///`SubscriptionError`, если не указан счёт, интервал или инструмент не торгуется
+    pub fn topic(&self, symbol: &str) -> Result<String, SubscriptionError> {
+        match self.channel {
+            Channel::Orders | Channel::Fills => self
+                .account
+                .clone()
+                .ok_or(SubscriptionError::MissingAccount),
+            Channel::Candles => {
+                let instrument = self.instrument(symbol)?;
+                let interval = self.interval.ok_or(SubscriptionError::MissingInterval)?;
+                Ok(format!("{instrument}:{}", interval.as_str()))
+            }
+            _ => self.instrument(symbol),
+        }
+    }
+
+    fn instrument(&self, symbol: &str) -> Result<String, SubscriptionError> {
+        match &self.instrument {
+            Some(instrument) if instrument == symbol => Ok(instrument.clone()),
+            _ => Err(SubscriptionError::UnknownInstrument),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SubscriptionRequest {
+    Subscribe {
+        request_id: Option<String>,
+        #[serde(flatten)]
+        subscription: Subscription,
+    },
+    Unsubscribe {
+        request_id: Option<String>,
+        #[serde(flatten)]
+        subscription: Subscription,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionError {
+    InvalidMessage,
+    UnknownInstrument,
+    MissingAccount,
+    MissingInterval,
+    AlreadySubscribed,
+    NotSubscribed,
+    Unauthorized,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SubscriptionResponse {
+    Subscribed {
+        request_id: Option<String>,
+        channel: Channel,
+        topic: String,
+    },
+    Unsubscribed {
+        request_id: Option<String>,
+        channel: Channel,
+        topic: String,
+    },
+    Error {
+        request_id: Option<String>,
+        reason: SubscriptionError,
+    },
+}
+
+///данные подписки; channel и topic позволяют разделить потоки одного соединения
+#[derive(Serialize)]
+pub struct ChannelMessage<'a, T> {
+    pub channel: Channel,
+    pub topic: &'a str,
+    #[serde(flatten)]
+    pub message: &'a FeedMessage<T>,
+}
+
+///то же сообщение на стороне клиента, после декодирования
+#[derive(Debug, Deserialize)]
+pub struct ChannelFrame<T> {
+    pub channel: Channel,
+    pub topic: String,
+    #[serde(flatten)]
+    pub message: FeedMessage<T>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_subscribe_with_depth_options() {
+        let json = r#"{"op":"subscribe","request_id":"s1","channel":"depth","instrument":"BTCUSDT","depth":10,"group":5}"#;
+
+        let request: SubscriptionRequest = serde_json::from_str(json).unwrap();
+
+        let SubscriptionRequest::Subscribe {
+            request_id,
+            subscription,
+        } = request
+        else {
+            panic!("expected subscribe request");
+        };
+        assert_eq!(request_id.as_deref(), Some("s1"));
+        assert_eq!(subscription.channel, Channel::Depth);
+        assert_eq!(subscription.depth.depth, Some(10));
+        assert_eq!(subscription.depth.group, Some(5));
+        assert_eq!(subscription.conflation(), None);
+        assert_eq!(subscription.topic("BTCUSDT"), Ok(String::from("BTCUSDT")));
+    }
+
+    #[test]
+    fn test_topic_errors() {
+        let json = r#"{"op":"unsubscribe","channel":"trades","instrument":"ETHUSDT","history":20}"#;
+        let SubscriptionRequest::Unsubscribe { subscription, .. } =
+            serde_json::from_str(json).unwrap()
+        else {
+            panic!("expected unsubscribe request");
+        };
+        assert_eq!(subscription.history, Some(20));
+        assert_eq!(
+            subscription.topic("BTCUSDT"),
+            Err(SubscriptionError::UnknownInstrument)
+        );
+
+        let json = r#"{"op":"subscribe","channel":"orders"}"#;
+        let SubscriptionRequest::Subscribe { subscription, .. } =
+            serde_json::from_str(json).unwrap()
+        else {
+            panic!("expected subscribe request");
+        };
+        assert_eq!(
+            subscription.topic("BTCUSDT"),
+            Err(SubscriptionError::MissingAccount)
+        );
+    }
+
+    #[test]
+    fn test_candles_topic() {
+        let json =
+            r#"{"op":"subscribe","channel":"candles","instrument":"BTCUSDT","interval":"5m"}"#;
+        let SubscriptionRequest::Subscribe { subscription, .. } =
+            serde_json::from_str(json).unwrap()
+        else {
+            panic!("expected subscribe request");
+        };
+        assert_eq!(subscription.interval, Some(CandleInterval::FiveMinutes));
+        assert_eq!(
+            subscription.topic("BTCUSDT"),
+            Ok(String::from("BTCUSDT:5m"))
+        );
+
+        let json = r#"{"op":"subscribe","channel":"candles","instrument":"BTCUSDT"}"#;
+        let SubscriptionRequest::Subscribe { subscription, .. } =
+            serde_json::from_str(json).unwrap()
+        else {
+            panic!("expected subscribe request");
+        };
+        assert_eq!(
+            subscription.topic("BTCUSDT"),
+            Err(SubscriptionError::MissingInterval)
+        );
+    }
+
+    #[test]
+    fn test_conflation_interval() {
+        let json =
+            r#"{"op":"subscribe","channel":"depth","instrument":"BTCUSDT","conflation_ms":100}"#;
+        let SubscriptionRequest::Subscribe { subscription, .. } =
+            serde_json::from_str(json).unwrap()
+        else {
+            panic!("expected subscribe request");
+        };
+        assert_eq!(subscription.conflation(), Some(Duration::from_millis(100)));
+
+        let json =
+            r#"{"op":"subscribe","channel":"depth","instrument":"BTCUSDT","conflation_ms":0}"#;
+        let SubscriptionRequest::Subscribe { subscription, .. } =
+            serde_json::from_str(json).unwrap()
+        else {
+            panic!("expected subscribe request");
+        };
+        assert_eq!(subscription.conflation(), None);
+    }
+
+    #[test]
+    fn test_deserialize_unknown_channel() {
+        let json = r#"{"op":"subscribe","channel":"news","instrument":"BTCUSDT"}"#;
+
+        assert!(serde_json::from_str::<SubscriptionRequest>(json).is_err());
+    }
+
+    #[test]
+    fn test_serialize_channel_message() {
+        let feed_message = FeedMessage::update(3, vec![1, 2]);
+        let message = ChannelMessage {
+            channel: Channel::MarketByOrder,
+            topic: "BTCUSDT",
+            message: &feed_message,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"channel":"market_by_order","topic":"BTCUSDT","seq":3,"type":"update","data":[1,2]}"#
+        );
+    }
+}
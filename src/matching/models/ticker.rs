@@ -0,0 +1,230 @@
+use crate::matching::models::trade_feed::PublicTrade;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+///статистика за скользящее окно; цены отсутствуют, если в окне не было сделок.
+///`quote_volume` - сумма price * quantity, vwap - `quote_volume` / volume
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ticker {
+    pub open: Option<u32>,
+    pub high: Option<u32>,
+    pub low: Option<u32>,
+    pub last: Option<u32>,
+    pub volume: u64,
+    pub quote_volume: u64,
+    pub trades: u64,
+    pub price_change: Option<i64>,
+    pub price_change_percent: Option<f64>,
+    pub vwap: Option<f64>,
+}
+
+struct TickerBucket {
+    second: i64,
+    open: u32,
+    close: u32,
+    volume: u64,
+    quote_volume: u64,
+    trades: u64,
+}
+
+///сделки копятся в посекундных корзинах: суммы меняются при добавлении и вытеснении корзины,
+///high и low берутся из монотонных очередей, поэтому окно никогда не пересчитывается целиком
+pub struct RollingTicker {
+    window: TimeDelta,
+    buckets: VecDeque<TickerBucket>,
+    highs: VecDeque<(i64, u32)>, //цены по убыванию, первая - максимум окна
+    lows: VecDeque<(i64, u32)>,  //цены по возрастанию, первая - минимум окна
+    volume: u64,
+    quote_volume: u64,
+    trades: u64,
+}
+
+impl RollingTicker {
+    #[must_use]
+    pub const fn new(window: TimeDelta) -> Self {
+        Self {
+            window,
+            buckets: VecDeque::new(),
+            highs: VecDeque::new(),
+            lows: VecDeque::new(),
+            volume: 0,
+            quote_volume: 0,
+            trades: 0,
+        }
+    }
+
+    ///сделка с временем раньше последней корзины относится к последней корзине
+    pub fn add(&mut self, trade: &PublicTrade) {
+        let quantity = u64::from(trade.quantity);
+        let quote_volume = u64::from(trade.price) * quantity;
+        let second = trade.time.timestamp();
+        let second = self
+            .buckets
+            .back()
+            .map_or(second, |last| last.second.max(second));
+
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.second == second => {
+                bucket.close = trade.price;
+                bucket.volume += quantity;
+                bucket.quote_volume = bucket.quote_volume.saturating_add(quote_volume);
+                bucket.trades += 1;
+            }
+            _ => self.buckets.push_back(TickerBucket {
+                second,
+                open: trade.price,
+                close: trade.price,
+                volume: quantity,
+                quote_volume,
+                trades: 1,
+            }),
+        }
+        self.volume += quantity;
+        self.quote_volume = self.quote_volume.saturating_add(quote_volume);
+        self.trades += 1;
+
+        while self
+            .highs
+            .back()
+            .is_some_and(|&(_, price)| price <= trade.price)
+        {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((second, trade.price));
+        while self
+            .lows
+            .back()
+            .is_some_and(|&(_, price)| price >= trade.price)
+        {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((second, trade.price));
+    }
+
+    ///вытесняет корзины, вышедшие из окна, которое заканчивается в now
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        let start = (now - self.window).timestamp();
+        while let Some(bucket) = self.buckets.front() {
+            if bucket.second > start {
+                break;
+            }
+            self.volume -= bucket.volume;
+            self.quote_volume -= bucket.quote_volume;
+            self.trades -= bucket.trades;
+            self.buckets.pop_front();
+        }
+        while self
+            .highs
+            .front()
+            .is_some_and(|&(second, _)| second <= start)
+        {
+            self.highs.pop_front();
+        }
+        while self
+            .lows
+            .front()
+            .is_some_and(|&(second, _)| second <= start)
+        {
+            self.lows.pop_front();
+        }
+    }
+
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "процент и vwap - справочные значения"
+    )]
+    #[must_use]
+    pub fn ticker(&self) -> Ticker {
+        let open = self.buckets.front().map(|bucket| bucket.open);
+        let last = self.buckets.back().map(|bucket| bucket.close);
+        let price_change = open
+            .zip(last)
+            .map(|(open, last)| i64::from(last) - i64::from(open));
+        Ticker {
+            open,
+            high: self.highs.front().map(|&(_, price)| price),
+            low: self.lows.front().map(|&(_, price)| price),
+            last,
+            volume: self.volume,
+            quote_volume: self.quote_volume,
+            trades: self.trades,
+            price_change,
+            price_change_percent: open
+                .zip(price_change)
+                .filter(|&(open, _)| open > 0)
+                .map(|(open, change)| change as f64 * 100.0 / f64::from(open)),
+            vwap: (self.volume > 0).then(|| self.quote_volume as f64 / self.volume as f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_side::OrderSide;
+
+    fn time(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    fn trade(seconds: i64, price: u32, quantity: u32) -> PublicTrade {
+        PublicTrade {
+            id: 1,
+            price,
+            quantity,
+            side: OrderSide::Bid,
+            time: time(seconds),
+        }
+    }
+
+    #[test]
+    fn test_empty_window() {
+        let ticker = RollingTicker::new(TimeDelta::hours(24)).ticker();
+
+        assert_eq!(ticker, Ticker::default());
+    }
+
+    #[test]
+    fn test_statistics() {
+        let mut rolling = RollingTicker::new(TimeDelta::hours(24));
+        rolling.add(&trade(10, 100, 2));
+        rolling.add(&trade(10, 120, 1));
+        rolling.add(&trade(20, 90, 3));
+        rolling.add(&trade(30, 110, 4));
+
+        let ticker = rolling.ticker();
+        assert_eq!(ticker.open, Some(100));
+        assert_eq!(ticker.high, Some(120));
+        assert_eq!(ticker.low, Some(90));
+        assert_eq!(ticker.last, Some(110));
+        assert_eq!(ticker.volume, 10);
+        assert_eq!(ticker.quote_volume, 200 + 120 + 270 + 440);
+        assert_eq!(ticker.trades, 4);
+        assert_eq!(ticker.price_change, Some(10));
+        assert_eq!(ticker.price_change_percent, Some(10.0));
+        assert_eq!(ticker.vwap, Some(103.0));
+    }
+
+    #[test]
+    fn test_window_rolls() {
+        let mut rolling = RollingTicker::new(TimeDelta::seconds(60));
+        rolling.add(&trade(0, 150, 1));
+        rolling.add(&trade(30, 80, 1));
+        rolling.add(&trade(50, 100, 2));
+
+        rolling.expire(time(59));
+        assert_eq!(rolling.ticker().trades, 3);
+
+        rolling.expire(time(60));
+        let ticker = rolling.ticker();
+        assert_eq!(ticker.open, Some(80));
+        assert_eq!(ticker.high, Some(100));
+        assert_eq!(ticker.low, Some(80));
+        assert_eq!(ticker.volume, 3);
+        assert_eq!(ticker.trades, 2);
+
+        rolling.expire(time(110));
+        assert_eq!(rolling.ticker(), Ticker::default());
+    }
+}
@@ -0,0 +1,68 @@
+use crate::matching::models::deal::Deal;
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::fee_schedule::Liquidity;
+use crate::matching::models::order_side::OrderSide;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+///сделка в публичной ленте: без ордеров, по которым можно узнать участников; side - сторона агрессора
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicTrade {
+    pub id: u64,
+    pub price: u32,
+    pub quantity: u32,
+    pub side: OrderSide,
+    pub time: DateTime<Utc>,
+}
+
+impl From<&Deal> for PublicTrade {
+    fn from(deal: &Deal) -> Self {
+        Self {
+            id: deal.id,
+            price: deal.price,
+            quantity: deal.quantity,
+            side: deal.side.clone(),
+            time: deal.time,
+        }
+    }
+}
+
+///исполнение в приватной ленте счёта: side и liquidity относятся к ордеру этого счёта
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateTrade {
+    pub id: u64,
+    pub order_id: Uuid,
+    pub side: OrderSide,
+    pub liquidity: Liquidity,
+    pub price: u32,
+    pub quantity: u32,
+    pub fee: u64,
+    pub ask_order: Uuid,
+    pub bid_order: Uuid,
+    pub time: DateTime<Utc>,
+}
+
+impl PrivateTrade {
+    ///только отчёты об исполнении несут сделку
+    #[must_use]
+    pub fn from_report(report: &ExecutionReport) -> Option<Self> {
+        let counter_order = report.counter_order_id?;
+        let (ask_order, bid_order) = match report.side {
+            OrderSide::Ask => (report.order_id, counter_order),
+            OrderSide::Bid => (counter_order, report.order_id),
+        };
+        Some(Self {
+            id: report.trade_id?,
+            order_id: report.order_id,
+            side: report.side.clone(),
+            liquidity: report.liquidity?,
+            price: report.last_price?,
+            quantity: report.last_quantity?,
+            fee: report.fee?,
+            ask_order,
+            bid_order,
+            time: report.time,
+        })
+    }
+}
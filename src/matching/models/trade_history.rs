@@ -0,0 +1,187 @@
+use crate::matching::models::trade_feed::PublicTrade;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+///from включительно, to исключая; cursor - id последней полученной сделки, страница начинается со следующей
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TradesQuery {
+    pub symbol: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub cursor: Option<u64>,
+}
+
+impl TradesQuery {
+    fn limit(&self) -> usize {
+        self.limit
+            .map_or(DEFAULT_PAGE_SIZE, |limit| limit.clamp(1, MAX_PAGE_SIZE))
+    }
+}
+
+///`next_cursor` есть, только если за страницей остались подходящие сделки
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradePage {
+    pub trades: Vec<PublicTrade>,
+    pub next_cursor: Option<u64>,
+}
+
+///последние сделки по возрастанию id; id идут подряд, поэтому разрыв между cursor и первой сделкой
+///страницы означает, что пропущенные сделки уже вытеснены из истории
+pub struct TradeHistory {
+    capacity: usize,
+    trades: VecDeque<PublicTrade>,
+}
+
+impl TradeHistory {
+    #[must_use]
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            trades: VecDeque::new(),
+        }
+    }
+
+    pub fn extend(&mut self, trades: impl IntoIterator<Item = PublicTrade>) {
+        self.trades.extend(trades);
+        while self.trades.len() > self.capacity {
+            self.trades.pop_front();
+        }
+    }
+
+    ///сделки упорядочены и по id, и по времени, поэтому границы страницы ищутся двоичным поиском
+    #[must_use]
+    pub fn page(&self, query: &TradesQuery) -> TradePage {
+        let after_cursor = query.cursor.map_or(0, |cursor| {
+            self.trades.partition_point(|trade| trade.id <= cursor)
+        });
+        let from = query.from.map_or(0, |from| {
+            self.trades.partition_point(|trade| trade.time < from)
+        });
+        let end = query.to.map_or(self.trades.len(), |to| {
+            self.trades.partition_point(|trade| trade.time < to)
+        });
+        let start = after_cursor.max(from).min(end);
+        let page_end = end.min(start.saturating_add(query.limit()));
+        let trades: Vec<PublicTrade> = self.trades.range(start..page_end).cloned().collect();
+        let next_cursor = match trades.last() {
+            Some(last) if page_end < end => Some(last.id),
+            _ => None,
+        };
+
+        TradePage {
+            trades,
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_side::OrderSide;
+    use chrono::TimeDelta;
+
+    fn history(capacity: usize, count: u64, start: DateTime<Utc>) -> TradeHistory {
+        let mut history = TradeHistory::new(capacity);
+        history.extend((1..=count).map(|id| PublicTrade {
+            id,
+            price: 100,
+            quantity: 1,
+            side: OrderSide::Bid,
+            time: start + TimeDelta::seconds(i64::try_from(id).unwrap()),
+        }));
+        history
+    }
+
+    fn ids(page: &TradePage) -> Vec<u64> {
+        page.trades.iter().map(|trade| trade.id).collect()
+    }
+
+    #[test]
+    fn test_cursor_pagination() {
+        let history = history(100, 5, Utc::now());
+        let mut query = TradesQuery {
+            limit: Some(2),
+            ..TradesQuery::default()
+        };
+
+        let first = history.page(&query);
+        assert_eq!(ids(&first), vec![1, 2]);
+        assert_eq!(first.next_cursor, Some(2));
+
+        query.cursor = first.next_cursor;
+        let second = history.page(&query);
+        assert_eq!(ids(&second), vec![3, 4]);
+
+        query.cursor = second.next_cursor;
+        let last = history.page(&query);
+        assert_eq!(ids(&last), vec![5]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn test_time_range() {
+        let start = Utc::now();
+        let history = history(100, 10, start);
+        let query = TradesQuery {
+            from: Some(start + TimeDelta::seconds(3)),
+            to: Some(start + TimeDelta::seconds(6)),
+            ..TradesQuery::default()
+        };
+
+        let page = history.page(&query);
+        assert_eq!(ids(&page), vec![3, 4, 5]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn test_time_range_pagination() {
+        let start = Utc::now();
+        let history = history(100, 10, start);
+        let mut query = TradesQuery {
+            from: Some(start + TimeDelta::seconds(3)),
+            to: Some(start + TimeDelta::seconds(8)),
+            limit: Some(3),
+            ..TradesQuery::default()
+        };
+
+        let first = history.page(&query);
+        assert_eq!(ids(&first), vec![3, 4, 5]);
+        assert_eq!(first.next_cursor, Some(5));
+
+        query.cursor = first.next_cursor;
+        let last = history.page(&query);
+        assert_eq!(ids(&last), vec![6, 7]);
+        assert_eq!(last.next_cursor, None);
+
+        query.cursor = Some(9);
+        assert!(history.page(&query).trades.is_empty());
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let history = history(3, 5, Utc::now());
+
+        let page = history.page(&TradesQuery {
+            cursor: Some(1),
+            ..TradesQuery::default()
+        });
+        assert_eq!(ids(&page), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_limit_is_clamped() {
+        let history = history(100, 3, Utc::now());
+        let page = history.page(&TradesQuery {
+            limit: Some(0),
+            ..TradesQuery::default()
+        });
+        assert_eq!(ids(&page), vec![1]);
+        assert_eq!(page.next_cursor, Some(1));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::order_message::OrderMessage;
+use crate::matching::models::order_record::OrderRecord;
+use crate::matching::models::reject_reason::RejectReason;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TradeRequest {
+    Place {
+        request_id: String,
+        #[serde(flatten)]
+        order: OrderMessage,
+    },
+    Cancel {
+        request_id: String,
+        id: Uuid,
+    },
+    Amend {
+        request_id: String,
+        id: Uuid,
+        price: Option<u32>,
+        quantity: Option<u32>,
+    },
+}
+
+impl TradeRequest {
+    #[must_use]
+    pub fn request_id(&self) -> &str {
+        match self {
+            Self::Place { request_id, .. }
+            | Self::Cancel { request_id, .. }
+            | Self::Amend { request_id, .. } => request_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TradeResponse {
+    Ack {
+        request_id: String,
+        order: OrderRecord,
+    },
+    Reject {
+        request_id: Option<String>,
+        reason: RejectReason,
+    },
+    ExecutionReport(ExecutionReport),
+    ///после отставания сессии: открытые ордера счёта на момент отчёта seq, более ранние отчёты в них уже учтены
+    OpenOrders {
+        seq: u64,
+        orders: Vec<OrderRecord>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_side::OrderSide;
+
+    #[test]
+    fn test_deserialize_place() {
+        let id = Uuid::new_v4();
+        let json = format!(
+            r#"{{"type":"place","request_id":"r1","id":"{id}","side":"Bid","quantity":10,"price":500}}"#
+        );
+
+        let request: TradeRequest = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(request.request_id(), "r1");
+        let TradeRequest::Place { order, .. } = request else {
+            panic!("expected place request");
+        };
+        assert_eq!(order.id, id);
+        assert_eq!(order.side, OrderSide::Bid);
+        assert_eq!(order.quantity, 10);
+        assert_eq!(order.price, 500);
+        assert_eq!(order.account, None);
+    }
+
+    #[test]
+    fn test_deserialize_amend_without_price() {
+        let id = Uuid::new_v4();
+        let json = format!(r#"{{"type":"amend","request_id":"r2","id":"{id}","quantity":5}}"#);
+
+        let request: TradeRequest = serde_json::from_str(&json).unwrap();
+
+        let TradeRequest::Amend {
+            price, quantity, ..
+        } = request
+        else {
+            panic!("expected amend request");
+        };
+        assert_eq!(price, None);
+        assert_eq!(quantity, Some(5));
+    }
+
+    #[test]
+    fn test_deserialize_unknown_type() {
+        let json = r#"{"type":"replace","request_id":"r3"}"#;
+
+        assert!(serde_json::from_str::<TradeRequest>(json).is_err());
+    }
+
+    #[test]
+    fn test_serialize_reject() {
+        let response = TradeResponse::Reject {
+            request_id: Some(String::from("r4")),
+            reason: RejectReason::UnknownOrder,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"type":"reject","request_id":"r4","reason":"unknown_order"}"#
+        );
+    }
+}
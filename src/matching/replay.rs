@@ -0,0 +1,39 @@
+use crate::matching::models::engine_command::EngineCommand;
+use crate::matching::models::trade_feed::PublicTrade;
+use crate::matching::models::trade_history::TradesQuery;
+use tokio::sync::{mpsc, oneshot};
+
+///сделки с id больше after из истории движка, постранично; None - движок недоступен.
+///Если часть пропущенных сделок уже вытеснена из истории, возвращается то, что осталось
+pub async fn missed_trades(
+    engine: &mpsc::Sender<EngineCommand>,
+    after: u64,
+) -> Option<Vec<PublicTrade>> {
+    let mut trades: Vec<PublicTrade> = Vec::new();
+    let mut cursor = Some(after);
+    while let Some(cursor_id) = cursor {
+        let (reply, reply_receiver) = oneshot::channel();
+        let query = TradesQuery {
+            limit: Some(usize::MAX),
+            cursor: Some(cursor_id),
+            ..TradesQuery::default()
+        };
+        engine
+            .send(EngineCommand::GetTrades { query, reply })
+            .await
+            .ok()?;
+        let page = reply_receiver.await.ok()?;
+        cursor = page.next_cursor;
+        trades.extend(page.trades);
+    }
+    if let Some(first) = trades.first()
+        && first.id > after + 1
+    {
+        eprintln!(
+            "Trades {}..{} are no longer in the history",
+            after + 1,
+            first.id - 1
+        );
+    }
+    Some(trades)
+}
@@ -1,25 +1,439 @@
 use crate::DealBook;
-use crate::DepthOfMarket;
-use tokio::sync::broadcast;
-
-pub fn send_data(
-    dom_sender: &broadcast::Sender<DepthOfMarket>,
-    orderbook: DepthOfMarket,
-    db_sender: &broadcast::Sender<DealBook>,
-    dealbook: DealBook,
-) {
-    send_deals(db_sender, dealbook);
-    send_orderbook(dom_sender, orderbook);
+use crate::frame::{EncodedFeed, SharedFeed};
+use crate::matching::models::best_bid_offer::BestBidOffer;
+use crate::matching::models::depth_of_market::DepthChange;
+use crate::matching::models::execution_report::ExecutionReport;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::market_by_order::OrderEvent;
+use crate::matching::models::subscription_message::Channel;
+use crate::matching::models::trade_feed::PublicTrade;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+const RECENT_DEALS_SIZE: usize = 100;
+
+///рассылает рыночные данные с порядковыми номерами и хранит последние снимки для ресинхронизации;
+///каждое сообщение сериализуется здесь один раз, сессии пересылают готовые байты
+pub struct Publisher {
+    symbol: String,
+    dom_sender: broadcast::Sender<SharedFeed<Vec<DepthChange>>>,
+    dom_seq: u64,
+    mbo_sender: broadcast::Sender<SharedFeed<Vec<OrderEvent>>>,
+    mbo_seq: u64,
+    db_sender: broadcast::Sender<SharedFeed<Vec<PublicTrade>>>,
+    db_snapshot: watch::Sender<FeedMessage<Vec<PublicTrade>>>,
+    db_seq: u64,
+    recent_deals: VecDeque<PublicTrade>,
+    bbo: watch::Sender<SharedFeed<BestBidOffer>>, //подписчикам важно только последнее значение
+    bbo_seq: u64,
+    er_sender: broadcast::Sender<ExecutionReport>,
+    er_seq: u64,
 }
 
-fn send_deals(db_sender: &broadcast::Sender<DealBook>, dealbook: DealBook) {
-    if !dealbook.deals.is_empty() && db_sender.send(dealbook).is_err() {
-        println!("Error_dealbook");
+impl Publisher {
+    pub fn new(
+        symbol: &str,
+        dom_sender: broadcast::Sender<SharedFeed<Vec<DepthChange>>>,
+        mbo_sender: broadcast::Sender<SharedFeed<Vec<OrderEvent>>>,
+        db_sender: broadcast::Sender<SharedFeed<Vec<PublicTrade>>>,
+        db_snapshot: watch::Sender<FeedMessage<Vec<PublicTrade>>>,
+        bbo: watch::Sender<SharedFeed<BestBidOffer>>,
+        er_sender: broadcast::Sender<ExecutionReport>,
+    ) -> Self {
+        Self {
+            symbol: symbol.to_owned(),
+            dom_sender,
+            dom_seq: 0,
+            mbo_sender,
+            mbo_seq: 0,
+            db_sender,
+            db_snapshot,
+            db_seq: 0,
+            recent_deals: VecDeque::new(),
+            bbo,
+            bbo_seq: 0,
+            er_sender,
+            er_seq: 0,
+        }
+    }
+
+    ///номер последнего опубликованного изменения стакана, к которому привязываются снимки
+    pub const fn depth_seq(&self) -> u64 {
+        self.dom_seq
+    }
+
+    pub const fn market_by_order_seq(&self) -> u64 {
+        self.mbo_seq
+    }
+
+    ///номер последнего опубликованного отчёта об исполнении, к нему привязываются снимки открытых ордеров
+    pub const fn execution_report_seq(&self) -> u64 {
+        self.er_seq
+    }
+
+    pub fn send_data(
+        &mut self,
+        depth_changes: Vec<DepthChange>,
+        depth_checksum: u32,
+        order_events: Vec<OrderEvent>,
+        dealbook: DealBook,
+        best_bid_offer: BestBidOffer,
+        execution_reports: Vec<ExecutionReport>,
+    ) {
+        self.send_deals(dealbook);
+        self.send_orderbook(depth_changes, depth_checksum);
+        self.send_market_by_order(order_events);
+        self.send_best_bid_offer(best_bid_offer);
+        self.send_execution_reports(execution_reports);
+    }
+
+    fn send_deals(&mut self, dealbook: DealBook) {
+        if dealbook.deals.is_empty() {
+            return;
+        }
+
+        self.db_seq += 1;
+        let trades: Vec<PublicTrade> = dealbook
+            .deals
+            .into_iter()
+            .map(|deal| PublicTrade::from(&deal))
+            .collect();
+        self.recent_deals.extend(trades.iter().cloned());
+        while self.recent_deals.len() > RECENT_DEALS_SIZE {
+            self.recent_deals.pop_front();
+        }
+        self.db_snapshot.send_replace(FeedMessage::snapshot(
+            self.db_seq,
+            self.recent_deals.iter().cloned().collect(),
+        ));
+
+        let message = self.encode(Channel::Trades, FeedMessage::update(self.db_seq, trades));
+        if message.is_none_or(|message| self.db_sender.send(message).is_err()) {
+            println!("Error_dealbook");
+        }
+    }
+
+    fn send_orderbook(&mut self, depth_changes: Vec<DepthChange>, checksum: u32) {
+        if depth_changes.is_empty() {
+            return;
+        }
+
+        self.dom_seq += 1;
+        let message = self.encode(
+            Channel::Depth,
+            FeedMessage::update(self.dom_seq, depth_changes).with_checksum(checksum),
+        );
+        if message.is_none_or(|message| self.dom_sender.send(message).is_err()) {
+            println!("Error_orderbook");
+        }
+    }
+
+    fn send_market_by_order(&mut self, order_events: Vec<OrderEvent>) {
+        if order_events.is_empty() {
+            return;
+        }
+
+        self.mbo_seq += 1;
+        let message = self.encode(
+            Channel::MarketByOrder,
+            FeedMessage::update(self.mbo_seq, order_events),
+        );
+        if message.is_none_or(|message| self.mbo_sender.send(message).is_err()) {
+            println!("Error_market_by_order");
+        }
+    }
+
+    fn send_best_bid_offer(&mut self, best_bid_offer: BestBidOffer) {
+        if self.bbo.borrow().message.data == best_bid_offer {
+            return;
+        }
+
+        self.bbo_seq += 1;
+        if let Some(message) = self.encode(
+            Channel::Bbo,
+            FeedMessage::update(self.bbo_seq, best_bid_offer),
+        ) {
+            self.bbo.send_replace(message);
+        }
+    }
+
+    fn encode<T: Serialize>(
+        &self,
+        channel: Channel,
+        message: FeedMessage<T>,
+    ) -> Option<SharedFeed<T>> {
+        match EncodedFeed::new(channel, &self.symbol, message) {
+            Ok(encoded) => Some(Arc::new(encoded)),
+            Err(e) => {
+                eprintln!("Error serializing {channel:?} to JSON: {e:?}");
+                None
+            }
+        }
+    }
+
+    fn send_execution_reports(&mut self, execution_reports: Vec<ExecutionReport>) {
+        for mut execution_report in execution_reports {
+            self.er_seq += 1;
+            execution_report.seq = self.er_seq;
+            if self.er_sender.send(execution_report).is_err() {
+                println!("Error_execution_report");
+            }
+        }
     }
 }
 
-fn send_orderbook(dom_sender: &broadcast::Sender<DepthOfMarket>, orderbook: DepthOfMarket) {
-    if dom_sender.send(orderbook).is_err() {
-        println!("Error_orderbook");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Encoding;
+    use crate::matching::models::feed_message::FeedKind;
+    use crate::matching::models::order_message::OrderMessage;
+    use crate::matching::models::order_record::OrderRecord;
+    use crate::matching::models::order_side::OrderSide;
+    use crate::matching::models::subscription_message::{ChannelFrame, ChannelMessage};
+    use axum::extract::ws::Message;
+    use chrono::Utc;
+    use std::hint::black_box;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    const SYMBOL: &str = "BTCUSDT";
+
+    fn publisher() -> Publisher {
+        let (dom_sender, _) = broadcast::channel(16);
+        let (mbo_sender, _) = broadcast::channel(16);
+        let (db_sender, _) = broadcast::channel(16);
+        let (db_snapshot, _) = watch::channel(FeedMessage::snapshot(0, Vec::new()));
+        let bbo_snapshot = FeedMessage::snapshot(0, BestBidOffer::default());
+        let (bbo, _) = watch::channel(Arc::new(
+            EncodedFeed::new(Channel::Bbo, SYMBOL, bbo_snapshot).unwrap(),
+        ));
+        let (er_sender, _) = broadcast::channel(16);
+        Publisher::new(
+            SYMBOL,
+            dom_sender,
+            mbo_sender,
+            db_sender,
+            db_snapshot,
+            bbo,
+            er_sender,
+        )
+    }
+
+    fn depth_change(price: u32, quantity: u32) -> DepthChange {
+        DepthChange {
+            side: OrderSide::Ask,
+            price,
+            quantity,
+            orders: u32::from(quantity > 0),
+        }
+    }
+
+    #[test]
+    fn test_orderbook_sequence_increments() {
+        let mut publisher = publisher();
+        let mut dom_receiver = publisher.dom_sender.subscribe();
+
+        publisher.send_data(
+            vec![depth_change(500, 10)],
+            10,
+            Vec::new(),
+            DealBook::new(1, 1, Utc::now()),
+            BestBidOffer::default(),
+            Vec::new(),
+        );
+        publisher.send_data(
+            Vec::new(),
+            0,
+            Vec::new(),
+            DealBook::new(1, 1, Utc::now()),
+            BestBidOffer::default(),
+            Vec::new(),
+        );
+        publisher.send_data(
+            vec![depth_change(500, 0)],
+            0,
+            Vec::new(),
+            DealBook::new(1, 1, Utc::now()),
+            BestBidOffer::default(),
+            Vec::new(),
+        );
+
+        let first = dom_receiver.try_recv().unwrap();
+        let second = dom_receiver.try_recv().unwrap();
+        assert_eq!(first.message.seq, 1);
+        assert_eq!(second.message.seq, 2);
+        assert_eq!(second.message.kind, FeedKind::Update);
+        assert_eq!(second.message.data, vec![depth_change(500, 0)]);
+        assert_eq!(second.message.checksum, Some(0));
+        assert!(dom_receiver.try_recv().is_err());
+        assert_eq!(publisher.depth_seq(), 2);
+    }
+
+    #[test]
+    fn test_execution_reports_are_numbered() {
+        let mut publisher = publisher();
+        let mut er_receiver = publisher.er_sender.subscribe();
+        let order = OrderRecord::new(&OrderMessage {
+            id: Uuid::new_v4(),
+            account: Some(String::from("alice")),
+            side: OrderSide::Ask,
+            quantity: 10,
+            price: 500,
+        });
+
+        publisher.send_execution_reports(vec![ExecutionReport::amend(&order)]);
+        publisher.send_execution_reports(vec![
+            ExecutionReport::amend(&order),
+            ExecutionReport::cancel(&order),
+        ]);
+
+        let seqs: Vec<u64> = (0..3)
+            .map(|_| er_receiver.try_recv().unwrap().seq)
+            .collect();
+        assert_eq!(seqs, vec![1, 2, 3]);
+        assert_eq!(publisher.execution_report_seq(), 3);
+    }
+
+    #[test]
+    fn test_recent_deals_snapshot_is_bounded() {
+        let mut publisher = publisher();
+        let uuid = Uuid::new_v4();
+
+        for price in 0..150 {
+            let mut dealbook = DealBook::new(1, 1, Utc::now());
+            dealbook.push(OrderSide::Bid, price, 1, uuid, uuid);
+            publisher.send_data(
+                Vec::new(),
+                0,
+                Vec::new(),
+                dealbook,
+                BestBidOffer::default(),
+                Vec::new(),
+            );
+        }
+        publisher.send_data(
+            Vec::new(),
+            0,
+            Vec::new(),
+            DealBook::new(1, 1, Utc::now()),
+            BestBidOffer::default(),
+            Vec::new(),
+        );
+
+        let snapshot = publisher.db_snapshot.borrow().clone();
+        assert_eq!(snapshot.seq, 150);
+        assert_eq!(snapshot.kind, FeedKind::Snapshot);
+        assert_eq!(snapshot.data.len(), RECENT_DEALS_SIZE);
+        assert_eq!(snapshot.data[0].price, 50);
+        assert_eq!(snapshot.data[RECENT_DEALS_SIZE - 1].price, 149);
+    }
+
+    #[test]
+    fn test_best_bid_offer_published_only_on_change() {
+        let mut publisher = publisher();
+        let best_bid_offer = BestBidOffer {
+            bid_price: Some(490),
+            bid_quantity: Some(5),
+            ..BestBidOffer::default()
+        };
+
+        publisher.send_best_bid_offer(BestBidOffer::default());
+        assert_eq!(publisher.bbo.borrow().message.seq, 0);
+
+        publisher.send_best_bid_offer(best_bid_offer.clone());
+        publisher.send_best_bid_offer(best_bid_offer.clone());
+
+        let encoded = publisher.bbo.borrow().clone();
+        assert_eq!(encoded.message.seq, 1);
+        assert_eq!(encoded.message.data, best_bid_offer);
+    }
+
+    #[test]
+    fn test_messages_are_encoded_once_with_channel_envelope() {
+        let mut publisher = publisher();
+        let mut first_receiver = publisher.dom_sender.subscribe();
+        let mut second_receiver = publisher.dom_sender.subscribe();
+
+        publisher.send_orderbook(vec![depth_change(500, 10)], 42);
+
+        let first = first_receiver.try_recv().unwrap();
+        let second = second_receiver.try_recv().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(
+            first.json.as_str(),
+            r#"{"channel":"depth","topic":"BTCUSDT","seq":1,"type":"update","data":[{"side":"Ask","price":500,"quantity":10,"orders":1}],"checksum":42}"#
+        );
+    }
+
+    #[test]
+    fn test_message_pack_frame_is_encoded_once() {
+        let mut publisher = publisher();
+        let mut receiver = publisher.dom_sender.subscribe();
+
+        publisher.send_orderbook(vec![depth_change(500, 10)], 42);
+
+        let encoded = receiver.try_recv().unwrap();
+        let (Ok(Message::Binary(first)), Ok(Message::Binary(second))) = (
+            encoded.frame(Encoding::MessagePack),
+            encoded.frame(Encoding::MessagePack),
+        ) else {
+            panic!("expected binary frames");
+        };
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        let decoded: ChannelFrame<Vec<DepthChange>> = Encoding::MessagePack.decode(&first).unwrap();
+        assert_eq!(decoded.message, encoded.message);
+    }
+
+    ///запуск: `cargo test --release bench_fan_out -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_fan_out_to_1000_subscribers() {
+        const SUBSCRIBERS: usize = 1000;
+        const EVENTS: u64 = 200;
+        let changes: Vec<DepthChange> = (0..20).map(|i| depth_change(500 + i, 10 + i)).collect();
+
+        //как было: каждый подписчик получает свою копию и сериализует её сам
+        let (sender, _) = broadcast::channel(16);
+        let mut receivers: Vec<_> = (0..SUBSCRIBERS).map(|_| sender.subscribe()).collect();
+        let started = Instant::now();
+        for seq in 1..=EVENTS {
+            sender
+                .send(FeedMessage::update(seq, changes.clone()))
+                .unwrap();
+            for receiver in &mut receivers {
+                let message = receiver.try_recv().unwrap();
+                let json = serde_json::to_string(&ChannelMessage {
+                    channel: Channel::Depth,
+                    topic: SYMBOL,
+                    message: &message,
+                })
+                .unwrap();
+                black_box(Message::Text(json.into()));
+            }
+        }
+        let per_subscriber = started.elapsed();
+
+        //как стало: одна сериализация в Publisher, подписчики копируют ссылку на байты
+        let mut publisher = publisher();
+        let mut receivers: Vec<_> = (0..SUBSCRIBERS)
+            .map(|_| publisher.dom_sender.subscribe())
+            .collect();
+        let started = Instant::now();
+        for _ in 1..=EVENTS {
+            publisher.send_orderbook(changes.clone(), 0);
+            for receiver in &mut receivers {
+                let message = receiver.try_recv().unwrap();
+                black_box(Message::Text(message.json.clone()));
+            }
+        }
+        let shared = started.elapsed();
+
+        println!(
+            "{SUBSCRIBERS} subscribers, {EVENTS} depth events: per-subscriber serialization {per_subscriber:?}, shared bytes {shared:?}"
+        );
+        assert!(shared < per_subscriber);
     }
 }
@@ -0,0 +1,124 @@
+use crate::frame::{EncodedFeed, SharedFeed};
+use crate::matching::models::engine_command::EngineCommand;
+use crate::matching::models::feed_message::FeedMessage;
+use crate::matching::models::subscription_message::Channel;
+use crate::matching::models::ticker::{RollingTicker, Ticker};
+use crate::matching::models::trade_feed::PublicTrade;
+use crate::matching::replay::missed_trades;
+use chrono::{TimeDelta, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{MissedTickBehavior, interval};
+
+const TICKER_WINDOW: TimeDelta = TimeDelta::hours(24);
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(1);
+
+///ведёт статистику за 24 часа по ленте сделок; значение публикуется, только когда оно изменилось,
+///в том числе когда старые сделки выходят из окна. При отставании от ленты пропущенные сделки
+///берутся из истории движка
+pub async fn aggregate_ticker(
+    symbol: String,
+    engine: mpsc::Sender<EngineCommand>,
+    mut trades: broadcast::Receiver<SharedFeed<Vec<PublicTrade>>>,
+    sender: watch::Sender<SharedFeed<Ticker>>,
+) {
+    let mut rolling = RollingTicker::new(TICKER_WINDOW);
+    let mut seq = 0_u64;
+    let mut last_trade_id = 0_u64;
+    let mut ticker = interval(EXPIRE_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            received = trades.recv() => match received {
+                Ok(encoded) => add_trades(&mut rolling, &mut last_trade_id, &encoded.message.data),
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Ticker skipped {skipped} trade messages, restoring from trade history");
+                    let Some(missed) = missed_trades(&engine, last_trade_id).await else {
+                        return;
+                    };
+                    add_trades(&mut rolling, &mut last_trade_id, &missed);
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = ticker.tick() => {}
+        }
+        rolling.expire(Utc::now());
+
+        let next = rolling.ticker();
+        if sender.borrow().message.data == next {
+            continue;
+        }
+        seq += 1;
+        match EncodedFeed::new(Channel::Ticker, &symbol, FeedMessage::update(seq, next)) {
+            Ok(encoded) => {
+                sender.send_replace(Arc::new(encoded));
+            }
+            Err(e) => eprintln!("Error serializing ticker to JSON: {e:?}"),
+        }
+    }
+}
+
+///сделки, уже полученные из истории при восстановлении, лента присылает ещё раз
+fn add_trades(rolling: &mut RollingTicker, last_trade_id: &mut u64, trades: &[PublicTrade]) {
+    for trade in trades {
+        if trade.id > *last_trade_id {
+            rolling.add(trade);
+            *last_trade_id = trade.id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::models::order_side::OrderSide;
+    use crate::matching::models::trade_history::TradeHistory;
+
+    #[tokio::test]
+    async fn test_restore_after_lag() {
+        let trades: Vec<PublicTrade> = (1..=3)
+            .map(|id| PublicTrade {
+                id,
+                price: 100,
+                quantity: 1,
+                side: OrderSide::Bid,
+                time: Utc::now(),
+            })
+            .collect();
+        let (engine, mut commands) = mpsc::channel(1);
+        let mut history = TradeHistory::new(10);
+        history.extend(trades.iter().cloned());
+        tokio::spawn(async move {
+            while let Some(EngineCommand::GetTrades { query, reply }) = commands.recv().await {
+                let _ = reply.send(history.page(&query));
+            }
+        });
+        let (trade_sender, trade_receiver) = broadcast::channel(1);
+        for (seq, trade) in (1..).zip(&trades) {
+            let message = FeedMessage::update(seq, vec![trade.clone()]);
+            let encoded = EncodedFeed::new(Channel::Trades, "BTCUSDT", message).unwrap();
+            trade_sender.send(Arc::new(encoded)).unwrap();
+        }
+        let snapshot = FeedMessage::snapshot(0, Ticker::default());
+        let (sender, mut receiver) = watch::channel(Arc::new(
+            EncodedFeed::new(Channel::Ticker, "BTCUSDT", snapshot).unwrap(),
+        ));
+
+        tokio::spawn(aggregate_ticker(
+            String::from("BTCUSDT"),
+            engine,
+            trade_receiver,
+            sender,
+        ));
+
+        let restored = receiver.wait_for(|ticker| ticker.message.data.trades == 3);
+        tokio::time::timeout(Duration::from_secs(5), restored)
+            .await
+            .expect("ticker was not restored after the lag")
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(receiver.borrow().message.data.trades, 3);
+    }
+}
@@ -0,0 +1,254 @@
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::{MissedTickBehavior, interval, timeout};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SessionConfig {
+    pub heartbeat_interval: Duration,
+    pub heartbeat_timeout: Duration,
+    pub outbound_buffer: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    ClientClosed,
+    HeartbeatTimeout,
+    SlowConsumer,
+    Lagged,
+    ServerShutdown,
+}
+
+impl CloseReason {
+    ///клиент ушёл или перестал отвечать, а не сервер завершил сессию
+    pub const fn is_disconnect(self) -> bool {
+        matches!(
+            self,
+            Self::ClientClosed | Self::HeartbeatTimeout | Self::SlowConsumer
+        )
+    }
+
+    ///коды 4000-4999 зарезервированы протоколом для приложений
+    fn close_frame(self) -> Option<CloseFrame> {
+        let (code, reason) = match self {
+            Self::ClientClosed => return None,
+            Self::HeartbeatTimeout => (4001, "heartbeat timeout"),
+            Self::SlowConsumer => (4008, "slow consumer"),
+            Self::Lagged => (4009, "lagged, resubscribe"),
+            Self::ServerShutdown => (1001, "server shutdown"),
+        };
+        Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct SessionCounters {
+    active: AtomicUsize,
+    opened: AtomicU64,
+    heartbeat_timeouts: AtomicU64,
+    slow_consumer_evictions: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SessionStats {
+    pub active: usize,
+    pub opened: u64,
+    pub heartbeat_timeouts: u64,
+    pub slow_consumer_evictions: u64,
+}
+
+impl SessionCounters {
+    pub fn stats(&self) -> SessionStats {
+        SessionStats {
+            active: self.active.load(Ordering::Relaxed),
+            opened: self.opened.load(Ordering::Relaxed),
+            heartbeat_timeouts: self.heartbeat_timeouts.load(Ordering::Relaxed),
+            slow_consumer_evictions: self.slow_consumer_evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn open(&self) -> ActiveSession<'_> {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        ActiveSession { counters: self }
+    }
+
+    fn record_close(&self, reason: CloseReason) {
+        match reason {
+            CloseReason::HeartbeatTimeout => {
+                self.heartbeat_timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+            CloseReason::SlowConsumer => {
+                self.slow_consumer_evictions.fetch_add(1, Ordering::Relaxed);
+            }
+            CloseReason::ClientClosed | CloseReason::Lagged | CloseReason::ServerShutdown => {}
+        }
+    }
+}
+
+struct ActiveSession<'a> {
+    counters: &'a SessionCounters,
+}
+
+impl Drop for ActiveSession<'_> {
+    fn drop(&mut self) {
+        self.counters.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+///очередь исходящих сообщений сессии; переполнение означает, что клиент не успевает читать
+#[derive(Clone)]
+pub struct SessionSender {
+    outbound: mpsc::Sender<Message>,
+}
+
+impl SessionSender {
+    pub const fn new(outbound: mpsc::Sender<Message>) -> Self {
+        Self { outbound }
+    }
+
+    pub fn send(&self, message: Message) -> Result<(), CloseReason> {
+        self.outbound.try_send(message).map_err(|e| match e {
+            TrySendError::Full(_) => CloseReason::SlowConsumer,
+            TrySendError::Closed(_) => CloseReason::ClientClosed,
+        })
+    }
+}
+
+///ведёт websocket-сессию: пинги, таймаут по тишине, ограниченный буфер отправки и закрытие с причиной
+pub async fn run_session<F, Fut>(
+    socket: WebSocket,
+    config: SessionConfig,
+    counters: &SessionCounters,
+    handler: F,
+) -> CloseReason
+where
+    F: FnOnce(SessionSender, mpsc::Receiver<Message>) -> Fut,
+    Fut: Future<Output = CloseReason>,
+{
+    let _active = counters.open();
+    let (mut sink, mut stream) = socket.split();
+    let (outbound_sender, mut outbound_receiver) = mpsc::channel(config.outbound_buffer);
+    let (inbound_sender, inbound_receiver) = mpsc::channel(config.outbound_buffer);
+
+    let writer = async {
+        let mut heartbeat = interval(config.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let message = tokio::select! {
+                message = outbound_receiver.recv() => match message {
+                    Some(message) => message,
+                    None => return CloseReason::ServerShutdown,
+                },
+                _ = heartbeat.tick() => Message::Ping(Vec::new().into()),
+            };
+            if sink.send(message).await.is_err() {
+                return CloseReason::ClientClosed;
+            }
+        }
+    };
+
+    let reader = async {
+        loop {
+            match timeout(config.heartbeat_timeout, stream.next()).await {
+                Err(_) => return CloseReason::HeartbeatTimeout,
+                Ok(Some(Ok(message @ (Message::Text(_) | Message::Binary(_))))) => {
+                    let _ = inbound_sender.send(message).await;
+                }
+                Ok(Some(Ok(Message::Close(_)) | Err(_)) | None) => {
+                    return CloseReason::ClientClosed;
+                }
+                Ok(Some(Ok(_))) => {}
+            }
+        }
+    };
+
+    let session = handler(
+        SessionSender {
+            outbound: outbound_sender,
+        },
+        inbound_receiver,
+    );
+
+    let reason = tokio::select! {
+        reason = writer => reason,
+        reason = reader => reason,
+        reason = session => reason,
+    };
+
+    counters.record_close(reason);
+    let close = async {
+        if let Some(frame) = reason.close_frame() {
+            sink.send(Message::Close(Some(frame))).await?;
+        }
+        sink.close().await
+    };
+    let _ = timeout(config.heartbeat_interval, close).await;
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sender_reports_slow_consumer_when_buffer_is_full() {
+        let (outbound, _receiver) = mpsc::channel(1);
+        let sender = SessionSender { outbound };
+
+        assert_eq!(sender.send(Message::Text("first".into())), Ok(()));
+        assert_eq!(
+            sender.send(Message::Text("second".into())),
+            Err(CloseReason::SlowConsumer)
+        );
+    }
+
+    #[test]
+    fn test_sender_reports_closed_session() {
+        let (outbound, receiver) = mpsc::channel(1);
+        let sender = SessionSender { outbound };
+        drop(receiver);
+
+        assert_eq!(
+            sender.send(Message::Text("first".into())),
+            Err(CloseReason::ClientClosed)
+        );
+    }
+
+    #[test]
+    fn test_counters() {
+        let counters = SessionCounters::default();
+
+        let first = counters.open();
+        let second = counters.open();
+        counters.record_close(CloseReason::SlowConsumer);
+        drop(second);
+        counters.record_close(CloseReason::HeartbeatTimeout);
+        drop(first);
+        counters.open();
+
+        let stats = counters.stats();
+        assert_eq!(stats.active, 0);
+        assert_eq!(stats.opened, 3);
+        assert_eq!(stats.slow_consumer_evictions, 1);
+        assert_eq!(stats.heartbeat_timeouts, 1);
+    }
+
+    #[test]
+    fn test_close_frames() {
+        assert!(CloseReason::ClientClosed.close_frame().is_none());
+        assert_eq!(CloseReason::SlowConsumer.close_frame().unwrap().code, 4008);
+        assert_eq!(
+            CloseReason::HeartbeatTimeout.close_frame().unwrap().reason,
+            "heartbeat timeout"
+        );
+    }
+}